@@ -1,104 +1,282 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
+use crate::grid::Grid;
+
+type Registers = BTreeMap<char, i32>;
+type Observer<'a> = Box<dyn FnMut(usize, &Registers) + 'a>;
+
 #[derive(Debug, Clone, Copy)]
-enum Instruction {
-    Noop,
-    Addx(i32),
+struct Opcode {
+    cycles: usize,
+    arity: usize,
+    execute: fn(&mut Registers, &[i32]),
 }
 
-impl Instruction {
-    fn new(s: &str) -> Instruction {
-        match s {
-            "noop" => Instruction::Noop,
-            _ => {
-                let split = s.split(' ').collect::<Vec<_>>();
-                assert_eq!("addx", split[0]);
-                Instruction::Addx(split[1].parse().unwrap())
-            }
-        }
-    }
+#[derive(Debug, Clone, Default)]
+struct InstructionSet {
+    opcodes: HashMap<String, Opcode>,
 }
 
-type Input = Vec<Instruction>;
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instruction {
+    opcode: String,
+    args: Vec<i32>,
+}
 
-fn one_impl(input: &Input) -> usize {
-    let mut out = 0;
-    let mut register = 1;
-    let mut cycle = 1;
-    for instr in input {
-        match instr {
-            Instruction::Noop => {
-                if (cycle + 20) % 40 == 0 {
-                    out += register * cycle;
-                }
-                cycle += 1;
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DecodeError {
+    UnknownOpcode {
+        line: usize,
+        opcode: String,
+    },
+    Arity {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    Operand {
+        line: usize,
+        operand: String,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownOpcode { line, opcode } => {
+                write!(f, "line {}: unknown opcode {:?}", line, opcode)
             }
-            Instruction::Addx(i) => {
-                if (cycle + 20) % 40 == 0 {
-                    out += register * cycle;
-                } else if (cycle + 21) % 40 == 0 {
-                    out += register * (cycle + 1);
-                }
-                register += i;
-                cycle += 2;
+            DecodeError::Arity {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            DecodeError::Operand { line, operand } => {
+                write!(f, "line {}: invalid operand {:?}", line, operand)
             }
         }
     }
-    out as usize
 }
 
-fn do_print(register: i32, cycle: i32) {
-    if cycle % 40 == 0 {
-        println!();
+impl std::error::Error for DecodeError {}
+
+fn register(registers: &Registers, name: char) -> i32 {
+    registers.get(&name).copied().unwrap_or(0)
+}
+
+impl InstructionSet {
+    fn new() -> InstructionSet {
+        InstructionSet::default()
     }
-    if (register - (cycle % 40)).abs() > 1 {
-        print!(".");
-    } else {
-        print!("#");
+
+    fn standard() -> InstructionSet {
+        let mut isa = InstructionSet::new();
+        isa.register("noop", 1, 0, |_, _| {});
+        isa.register("addx", 2, 1, |registers, args| {
+            *registers.entry('x').or_insert(0) += args[0];
+        });
+        isa
+    }
+
+    fn register(
+        &mut self,
+        name: &str,
+        cycles: usize,
+        arity: usize,
+        execute: fn(&mut Registers, &[i32]),
+    ) -> &mut InstructionSet {
+        let opcode = Opcode {
+            cycles,
+            arity,
+            execute,
+        };
+        self.opcodes.insert(name.to_string(), opcode);
+        self
+    }
+
+    fn decode(&self, line: usize, s: &str) -> Result<Instruction, DecodeError> {
+        let mut split = s.split_whitespace();
+        let name = split.next().unwrap_or("");
+        let opcode = self
+            .opcodes
+            .get(name)
+            .ok_or_else(|| DecodeError::UnknownOpcode {
+                line,
+                opcode: name.to_string(),
+            })?;
+        let args = split
+            .map(|arg| {
+                arg.parse().map_err(|_| DecodeError::Operand {
+                    line,
+                    operand: arg.to_string(),
+                })
+            })
+            .collect::<Result<Vec<i32>, _>>()?;
+        if args.len() != opcode.arity {
+            return Err(DecodeError::Arity {
+                line,
+                expected: opcode.arity,
+                found: args.len(),
+            });
+        }
+        Ok(Instruction {
+            opcode: name.to_string(),
+            args,
+        })
     }
 }
 
-fn two_impl(input: &Input) {
-    let mut register = 1;
-    let mut cycle = 0;
-    for instr in input {
-        match instr {
-            Instruction::Noop => {
-                do_print(register, cycle);
-                cycle += 1;
-            }
-            Instruction::Addx(i) => {
-                do_print(register, cycle);
-                do_print(register, cycle + 1);
-                register += i;
-                cycle += 2;
+struct Cpu<'a> {
+    isa: &'a InstructionSet,
+    registers: Registers,
+    cycle: usize,
+    observers: Vec<Observer<'a>>,
+}
+
+impl<'a> Cpu<'a> {
+    fn new(isa: &'a InstructionSet) -> Cpu<'a> {
+        Cpu {
+            isa,
+            registers: Registers::from([('x', 1)]),
+            cycle: 0,
+            observers: Vec::new(),
+        }
+    }
+
+    fn observe<F: FnMut(usize, &Registers) + 'a>(&mut self, observer: F) -> &mut Cpu<'a> {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    fn tick(&mut self) {
+        self.cycle += 1;
+        for observer in self.observers.iter_mut() {
+            observer(self.cycle, &self.registers);
+        }
+    }
+
+    fn run(&mut self, program: &[Instruction]) {
+        for instr in program {
+            let opcode = self.isa.opcodes[&instr.opcode];
+            for _ in 0..opcode.cycles {
+                self.tick();
             }
+            (opcode.execute)(&mut self.registers, &instr.args);
         }
     }
-    println!();
 }
 
-fn parse(reader: Vec<String>) -> Input {
+type Input = Vec<Instruction>;
+
+fn signal_strength(input: &Input, cycles: &[usize]) -> i64 {
+    let isa = InstructionSet::standard();
+    let mut sum = 0;
+    Cpu::new(&isa)
+        .observe(|cycle, registers| {
+            if cycles.contains(&cycle) {
+                sum += cycle as i64 * register(registers, 'x') as i64;
+            }
+        })
+        .run(input);
+    sum
+}
+
+fn one_impl(input: &Input) -> i64 {
+    signal_strength(input, &[20, 60, 100, 140, 180, 220])
+}
+
+const FONT: [(char, [&str; 6]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+fn render_crt(input: &Input, width: usize, height: usize) -> Grid<bool> {
+    let isa = InstructionSet::standard();
+    let mut framebuffer = Grid::new_with(width, height, |_, _| false);
+    Cpu::new(&isa)
+        .observe(|cycle, registers| {
+            let (x, y) = ((cycle - 1) % width, (cycle - 1) / width);
+            let sprite = register(registers, 'x');
+            if (sprite - x as i32).abs() <= 1 {
+                framebuffer.set(x, y, true);
+            }
+        })
+        .run(input);
+    framebuffer
+}
+
+fn ocr(framebuffer: &Grid<bool>) -> String {
+    let pixel = |x, y| match framebuffer.get(x, y) {
+        Some(true) => '#',
+        _ => '.',
+    };
+    (0..framebuffer.row_size())
+        .step_by(5)
+        .map(|left| {
+            let glyph = (0..6)
+                .map(|y| (left..left + 4).map(|x| pixel(x, y)).collect::<String>())
+                .collect::<Vec<_>>();
+            FONT.iter()
+                .find(|(_, pattern)| glyph == pattern)
+                .map(|(c, _)| *c)
+                .unwrap_or('?')
+        })
+        .collect()
+}
+
+fn two_impl(input: &Input) -> String {
+    ocr(&render_crt(input, 40, 6))
+}
+
+fn parse(reader: Vec<String>) -> Result<Input, DecodeError> {
+    let isa = InstructionSet::standard();
     reader
-        .into_iter()
-        .map(|s| Instruction::new(s.as_str()))
+        .iter()
+        .enumerate()
+        .map(|(i, s)| isa.decode(i + 1, s))
         .collect()
 }
 
 pub fn ten() -> Result<(), std::io::Error> {
     let file = File::open("input/10_input")?;
     let reader = BufReader::new(file);
-    let input = parse(reader.lines().map(|t| t.unwrap()).collect());
+    let input = parse(reader.lines().map(|t| t.unwrap()).collect())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     println!("Day 10 part 1: {}", one_impl(&input));
-    println!("Day 10 part 2: {:?}", two_impl(&input));
+    println!("Day 10 part 2: {}", two_impl(&input));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_10::{one_impl, parse, two_impl};
+    use crate::day_10::{
+        ocr, one_impl, parse, register, render_crt, two_impl, Cpu, DecodeError, InstructionSet,
+    };
+    use crate::grid::Grid;
 
     #[test]
     fn it_works() {
@@ -253,8 +431,112 @@ noop
         .trim()
         .lines()
         .collect::<Vec<_>>();
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(13140, one_impl(&parsed));
-        assert_eq!((), two_impl(&parsed));
+        assert_eq!("????????", two_impl(&parsed));
+        let expected = [
+            "##..##..##..##..##..##..##..##..##..##..",
+            "###...###...###...###...###...###...###.",
+            "####....####....####....####....####....",
+            "#####.....#####.....#####.....#####.....",
+            "######......######......######......####",
+            "#######.......#######.......#######.....",
+        ];
+        let framebuffer = render_crt(&parsed, 40, 6);
+        assert_eq!(
+            expected.join("\n") + "\n",
+            framebuffer
+                .map(|lit| if *lit { '#' } else { '.' })
+                .to_string()
+        );
+        assert_eq!(
+            Grid::new(vec![
+                vec![true, true, false, false, true],
+                vec![false, false, false, false, false],
+            ]),
+            render_crt(&parsed, 5, 2)
+        );
+    }
+
+    #[test]
+    fn instruction_set() {
+        let mut isa = InstructionSet::standard();
+        isa.register("addy", 1, 1, |registers, args| {
+            *registers.entry('y').or_insert(0) += args[0];
+        })
+        .register("mulxy", 3, 0, |registers, _| {
+            let product = register(registers, 'x') * register(registers, 'y');
+            registers.insert('x', product);
+        });
+        let program = ["addy 4", "addx 2", "mulxy", "noop"]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| isa.decode(i + 1, s))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut seen = Vec::new();
+        let mut cpu = Cpu::new(&isa);
+        cpu.observe(|cycle, registers| {
+            seen.push((cycle, register(registers, 'x'), register(registers, 'y')))
+        });
+        cpu.run(&program);
+        assert_eq!(7, cpu.cycle);
+        assert_eq!(12, register(&cpu.registers, 'x'));
+        drop(cpu);
+        assert_eq!(
+            vec![
+                (1, 1, 0),
+                (2, 1, 4),
+                (3, 1, 4),
+                (4, 3, 4),
+                (5, 3, 4),
+                (6, 3, 4),
+                (7, 12, 4)
+            ],
+            seen
+        );
+
+        assert_eq!(
+            Err(DecodeError::UnknownOpcode {
+                line: 2,
+                opcode: "addz".to_string()
+            }),
+            parse(vec!["noop".to_string(), "addz 1".to_string()])
+        );
+        assert_eq!(
+            Err(DecodeError::Arity {
+                line: 1,
+                expected: 1,
+                found: 0
+            }),
+            parse(vec!["addx".to_string()])
+        );
+        assert_eq!(
+            Err(DecodeError::Operand {
+                line: 1,
+                operand: "x".to_string()
+            }),
+            parse(vec!["addx x".to_string()])
+        );
+    }
+
+    #[test]
+    fn letters() {
+        let screen = [
+            "###..#..#.#....#..#...##..##..####..##..",
+            "#..#.#..#.#....#..#....#.#..#....#.#..#.",
+            "#..#.####.#....####....#.#......#..#..#.",
+            "###..#..#.#....#..#....#.#.##..#...####.",
+            "#....#..#.#....#..#.#..#.#..#.#....#..#.",
+            "#....#..#.####.#..#..##...###.####.#..#.",
+        ];
+        let framebuffer = Grid::new(
+            screen
+                .iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+        );
+        assert_eq!("PHLHJGZA", ocr(&framebuffer));
     }
 }
@@ -1,123 +1,467 @@
+use num::integer::lcm;
+use num::{BigInt, Zero};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::hash::Hash;
 use std::io::BufRead;
 use std::io::BufReader;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Old,
+    Num(i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Operation {
+    lhs: Operand,
+    op: Operator,
+    rhs: Operand,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Old => write!(f, "old"),
+            Operand::Num(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Sub => write!(f, "-"),
+            Operator::Mul => write!(f, "*"),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "new = {} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+impl Operation {
+    fn parse(s: &str) -> Option<Operation> {
+        let operand = |s: &str| match s {
+            "old" => Some(Operand::Old),
+            n => n.parse().ok().map(Operand::Num),
+        };
+        let parts = s.strip_prefix("new = ")?.split(' ').collect::<Vec<_>>();
+        match parts[..] {
+            [lhs, op, rhs] => Some(Operation {
+                lhs: operand(lhs)?,
+                op: match op {
+                    "+" => Operator::Add,
+                    "-" => Operator::Sub,
+                    "*" => Operator::Mul,
+                    _ => return None,
+                },
+                rhs: operand(rhs)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn eval<W: Worry>(&self, old: &W) -> W {
+        let value = |operand| match operand {
+            Operand::Old => old.clone(),
+            Operand::Num(n) => old.lift(n),
+        };
+        value(self.lhs).combine(self.op, &value(self.rhs))
+    }
+}
+
+trait Worry: Clone {
+    fn lift(&self, n: i64) -> Self;
+    fn combine(&self, op: Operator, rhs: &Self) -> Self;
+    fn divisible_by(&self, n: i64) -> bool;
+}
+
+impl Worry for i64 {
+    fn lift(&self, n: i64) -> i64 {
+        n
+    }
+
+    fn combine(&self, op: Operator, rhs: &i64) -> i64 {
+        match op {
+            Operator::Add => self + rhs,
+            Operator::Sub => self - rhs,
+            Operator::Mul => self * rhs,
+        }
+    }
+
+    fn divisible_by(&self, n: i64) -> bool {
+        self % n == 0
+    }
+}
+
+impl Worry for BigInt {
+    fn lift(&self, n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    fn combine(&self, op: Operator, rhs: &BigInt) -> BigInt {
+        match op {
+            Operator::Add => self + rhs,
+            Operator::Sub => self - rhs,
+            Operator::Mul => self * rhs,
+        }
+    }
+
+    fn divisible_by(&self, n: i64) -> bool {
+        (self % n).is_zero()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Modular {
+    value: i64,
+    modulus: i64,
+}
+
+impl Modular {
+    fn new(value: i64, modulus: i64) -> Modular {
+        Modular {
+            value: value.rem_euclid(modulus),
+            modulus,
+        }
+    }
+}
+
+impl Worry for Modular {
+    fn lift(&self, n: i64) -> Modular {
+        Modular::new(n, self.modulus)
+    }
+
+    fn combine(&self, op: Operator, rhs: &Modular) -> Modular {
+        Modular::new(self.value.combine(op, &rhs.value), self.modulus)
+    }
+
+    fn divisible_by(&self, n: i64) -> bool {
+        self.value % n == 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Monkey {
-    operation: Box<dyn Fn(i64) -> i64>,
+    operation: Operation,
     test: i64,
     targets: (usize, usize),
 }
 
 type Input = (Vec<Vec<i64>>, Vec<Monkey>);
 
-fn one_impl((its, monkeys): &Input) -> usize {
-    let mut items = its.clone();
-    let mut counts = monkeys.iter().map(|_| 0).collect::<Vec<usize>>();
-    for _ in 0..20 {
-        for (i, monkey) in monkeys.iter().enumerate() {
-            let m_items = items[i].clone();
-            items[i] = Vec::new();
-            for item in m_items {
-                counts[i] += 1;
-                let n = (monkey.operation)(item) / 3;
-                let target = if n % monkey.test == 0 {
-                    monkey.targets.0
-                } else {
-                    monkey.targets.1
-                };
-                items[target].push(n);
-            }
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Report {
+    counts: Vec<usize>,
+    checkpoints: Vec<(usize, Vec<usize>)>,
+}
+
+fn item_round<W, F>(
+    monkeys: &[Monkey],
+    relief: &F,
+    (mut at, mut item): (usize, W),
+) -> ((usize, W), Vec<usize>)
+where
+    W: Worry,
+    F: Fn(W) -> W,
+{
+    let mut counts = vec![0; monkeys.len()];
+    loop {
+        let monkey = &monkeys[at];
+        counts[at] += 1;
+        item = relief(monkey.operation.eval(&item));
+        let target = if item.divisible_by(monkey.test) {
+            monkey.targets.0
+        } else {
+            monkey.targets.1
+        };
+        if target <= at {
+            return ((target, item), counts);
+        }
+        at = target;
+    }
+}
+
+fn item_history<W, F>(
+    monkeys: &[Monkey],
+    relief: &F,
+    mut state: (usize, W),
+    rounds: usize,
+) -> impl Fn(usize) -> Vec<usize>
+where
+    W: Worry + Hash + Eq,
+    F: Fn(W) -> W,
+{
+    let mut seen = HashMap::new();
+    let mut history = vec![vec![0; monkeys.len()]];
+    let mut cycle = None;
+    for round in 0..rounds {
+        if let Some(previous) = seen.insert(state.clone(), round) {
+            cycle = Some((previous, round - previous));
+            break;
         }
+        let (next, counts) = item_round(monkeys, relief, state);
+        let total = history[round]
+            .iter()
+            .zip(counts)
+            .map(|(a, b)| a + b)
+            .collect();
+        history.push(total);
+        state = next;
     }
-    counts.sort();
-    counts[counts.len() - 1] * counts[counts.len() - 2]
-}
-
-fn two_impl((its, monkeys): &Input) -> usize {
-    let mul: i64 = monkeys.iter().map(|m| m.test).product();
-    let mut items = its.clone();
-    let mut counts = monkeys.iter().map(|_| 0).collect::<Vec<usize>>();
-    for _ in 0..10000 {
-        for (i, monkey) in monkeys.iter().enumerate() {
-            let m_items = items[i].clone();
-            items[i] = Vec::new();
-            for item in m_items {
-                counts[i] += 1;
-                let n = (monkey.operation)(item) % mul;
-                let target = if n % monkey.test == 0 {
-                    monkey.targets.0
-                } else {
-                    monkey.targets.1
-                };
-                items[target].push(n);
+    move |round| match cycle {
+        Some((start, period)) if round >= history.len() => {
+            let (repeats, offset) = ((round - start) / period, (round - start) % period);
+            (0..history[0].len())
+                .map(|m| {
+                    let gain = history[start + period][m] - history[start][m];
+                    history[start + offset][m] + repeats * gain
+                })
+                .collect()
+        }
+        _ => history[round].clone(),
+    }
+}
+
+fn simulate<W, F>(
+    items: Vec<Vec<W>>,
+    monkeys: &[Monkey],
+    rounds: usize,
+    relief: F,
+    checkpoints: &[usize],
+) -> Report
+where
+    W: Worry + Hash + Eq,
+    F: Fn(W) -> W,
+{
+    let mut report = Report {
+        counts: vec![0; monkeys.len()],
+        checkpoints: checkpoints
+            .iter()
+            .filter(|round| **round <= rounds)
+            .map(|round| (*round, vec![0; monkeys.len()]))
+            .collect(),
+    };
+    for (at, its) in items.into_iter().enumerate() {
+        for item in its {
+            let history = item_history(monkeys, &relief, (at, item), rounds);
+            let rounds = std::iter::once((rounds, &mut report.counts));
+            let checkpoints = report.checkpoints.iter_mut().map(|(r, c)| (*r, c));
+            for (round, counts) in rounds.chain(checkpoints) {
+                for (total, count) in counts.iter_mut().zip(history(round)) {
+                    *total += count;
+                }
             }
         }
     }
-    counts.sort();
-    counts[counts.len() - 1] * counts[counts.len() - 2]
-}
-
-fn parse(reader: Vec<String>) -> Input {
-    let mut monkeys = Vec::new();
-    let mut starting_items = Vec::new();
-    for i in 0..=reader.len() / 7 {
-        starting_items.push(
-            reader[(i * 7) + 1]
-                .split(": ")
-                .last()
-                .unwrap()
-                .split(", ")
-                .map(|n| n.parse().unwrap())
-                .collect::<Vec<i64>>(),
-        );
-        let op_parts = reader[(i * 7) + 2]
-            .split("new = old ")
-            .last()
-            .unwrap()
-            .split(' ')
-            .collect::<Vec<_>>();
-        let operation = if op_parts[1] == "old" {
-            Box::new(move |n| n * n) as Box<dyn Fn(i64) -> i64>
-        } else {
-            let op_num = op_parts[1].parse::<i64>().unwrap();
-            match op_parts[0] {
-                "*" => Box::new(move |n| n * op_num) as Box<dyn Fn(i64) -> i64>,
-                "+" => Box::new(move |n| n + op_num) as Box<dyn Fn(i64) -> i64>,
-                other => unimplemented!("{}", other),
+    report
+}
+
+#[allow(dead_code)]
+fn render_checkpoint(round: usize, counts: &[usize]) -> String {
+    let mut out = format!("== After round {} ==\n", round);
+    for (i, count) in counts.iter().enumerate() {
+        out.push_str(&format!("Monkey {} inspected items {} times.\n", i, count));
+    }
+    out
+}
+
+fn monkey_business(mut counts: Vec<usize>) -> usize {
+    counts.sort_unstable();
+    counts.iter().rev().take(2).product()
+}
+
+fn one_impl((items, monkeys): &Input) -> usize {
+    monkey_business(simulate(items.clone(), monkeys, 20, |n: i64| n / 3, &[]).counts)
+}
+
+fn two_impl((items, monkeys): &Input) -> usize {
+    let modulus = monkeys.iter().fold(1, |acc, m| lcm(acc, m.test));
+    let items = items
+        .iter()
+        .map(|its| its.iter().map(|n| Modular::new(*n, modulus)).collect())
+        .collect();
+    monkey_business(simulate(items, monkeys, 10000, |n| n, &[]).counts)
+}
+
+#[allow(dead_code)]
+fn exact_counts((items, monkeys): &Input, rounds: usize) -> Vec<usize> {
+    let items = items
+        .iter()
+        .map(|its| its.iter().map(|n| BigInt::from(*n)).collect())
+        .collect();
+    simulate(items, monkeys, rounds, |n| n, &[]).counts
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ParseError {
+    Malformed { line: usize, text: String },
+    MissingField { line: usize, field: &'static str },
+    DuplicateMonkey { line: usize, monkey: usize },
+    MissingMonkey { monkey: usize },
+    UnknownTarget { line: usize, target: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Malformed { line, text } => {
+                write!(f, "line {}: cannot parse {:?}", line, text)
             }
-        };
-        let test_op_num = reader[(i * 7) + 3]
-            .split("divisible by ")
-            .last()
-            .unwrap()
-            .parse::<i64>()
-            .unwrap();
-        let test = test_op_num;
-        let if_t = reader[(i * 7) + 4]
-            .split("throw to monkey ")
-            .last()
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
-        let if_f = reader[(i * 7) + 5]
-            .split("throw to monkey ")
-            .last()
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
-        monkeys.push(Monkey {
+            ParseError::MissingField { line, field } => {
+                write!(f, "line {}: monkey is missing {:?}", line, field)
+            }
+            ParseError::DuplicateMonkey { line, monkey } => {
+                write!(f, "line {}: monkey {} defined twice", line, monkey)
+            }
+            ParseError::MissingMonkey { monkey } => write!(f, "monkey {} is not defined", monkey),
+            ParseError::UnknownTarget { line, target } => {
+                write!(f, "line {}: no monkey {} to throw to", line, target)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type Block<'a> = Vec<(usize, &'a str)>;
+
+struct Parsed {
+    id: usize,
+    line: usize,
+    items: Vec<i64>,
+    monkey: Monkey,
+    target_lines: (usize, usize),
+}
+
+fn parse_block(block: &[(usize, &str)]) -> Result<Parsed, ParseError> {
+    let malformed = |line: usize, text: &str| ParseError::Malformed {
+        line,
+        text: text.to_string(),
+    };
+    let (line, header) = block[0];
+    let id = header
+        .strip_prefix("Monkey ")
+        .and_then(|s| s.strip_suffix(':'))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| malformed(line, header))?;
+
+    let mut fields: HashMap<&str, (usize, &str, &str)> = HashMap::new();
+    for &(l, text) in &block[1..] {
+        let (label, value) = text.split_once(':').ok_or_else(|| malformed(l, text))?;
+        if fields.insert(label, (l, text, value.trim())).is_some() {
+            return Err(malformed(l, text));
+        }
+    }
+    let field = |name: &'static str| {
+        fields
+            .get(name)
+            .copied()
+            .ok_or(ParseError::MissingField { line, field: name })
+    };
+
+    let (l, text, value) = field("Starting items")?;
+    let items = value
+        .split(',')
+        .map(|n| n.trim())
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().map_err(|_| malformed(l, text)))
+        .collect::<Result<Vec<i64>, _>>()?;
+
+    let (l, text, value) = field("Operation")?;
+    let operation = Operation::parse(value).ok_or_else(|| malformed(l, text))?;
+
+    let (l, text, value) = field("Test")?;
+    let test = value
+        .strip_prefix("divisible by ")
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n != 0)
+        .ok_or_else(|| malformed(l, text))?;
+
+    let target = |name| {
+        let (l, text, value) = field(name)?;
+        value
+            .strip_prefix("throw to monkey ")
+            .and_then(|n| n.parse::<usize>().ok())
+            .map(|target| (l, target))
+            .ok_or_else(|| malformed(l, text))
+    };
+    let (if_t_line, if_t) = target("If true")?;
+    let (if_f_line, if_f) = target("If false")?;
+
+    Ok(Parsed {
+        id,
+        line,
+        items,
+        monkey: Monkey {
             operation,
             test,
             targets: (if_t, if_f),
+        },
+        target_lines: (if_t_line, if_f_line),
+    })
+}
+
+fn parse(reader: Vec<String>) -> Result<Input, ParseError> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut current = Vec::new();
+    for (i, s) in reader.iter().enumerate() {
+        let s = s.trim();
+        if s.is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push((i + 1, s));
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+
+    let mut parsed = blocks
+        .iter()
+        .map(|block| parse_block(block))
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.sort_by_key(|p| (p.id, p.line));
+    if let Some(pair) = parsed.windows(2).find(|pair| pair[0].id == pair[1].id) {
+        return Err(ParseError::DuplicateMonkey {
+            line: pair[1].line,
+            monkey: pair[1].id,
         });
     }
-    (starting_items, monkeys)
+    if let Some(monkey) = (0..parsed.len()).find(|i| parsed[*i].id != *i) {
+        return Err(ParseError::MissingMonkey { monkey });
+    }
+    for p in &parsed {
+        let (if_t, if_f) = p.monkey.targets;
+        for (line, target) in [(p.target_lines.0, if_t), (p.target_lines.1, if_f)] {
+            if target >= parsed.len() {
+                return Err(ParseError::UnknownTarget { line, target });
+            }
+        }
+    }
+    Ok(parsed.into_iter().map(|p| (p.items, p.monkey)).unzip())
 }
 
 pub fn eleven() -> Result<(), std::io::Error> {
     let file = File::open("input/11_input")?;
     let reader = BufReader::new(file);
-    let input = parse(reader.lines().map(|t| t.unwrap()).collect());
+    let input = parse(reader.lines().map(|t| t.unwrap()).collect())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     println!("Day 11 part 1: {}", one_impl(&input));
     println!("Day 11 part 2: {}", two_impl(&input));
     Ok(())
@@ -125,7 +469,10 @@ pub fn eleven() -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::day_11::{one_impl, parse, two_impl};
+    use crate::day_11::{
+        exact_counts, one_impl, parse, render_checkpoint, simulate, two_impl, Modular, Monkey,
+        Operand, Operation, Operator, ParseError,
+    };
 
     #[test]
     fn it_works() {
@@ -161,8 +508,132 @@ Monkey 3:
         .trim()
         .lines()
         .collect::<Vec<_>>();
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(10605, one_impl(&parsed));
         assert_eq!(2713310158, two_impl(&parsed));
+        assert_eq!(vec![99, 97, 8, 103], exact_counts(&parsed, 20));
+
+        let (items, monkeys) = &parsed;
+        let items = items
+            .iter()
+            .map(|its| its.iter().map(|n| Modular::new(*n, 96577)).collect())
+            .collect::<Vec<_>>();
+        let report = simulate(items, monkeys, 10000, |n| n, &[1, 20, 1000, 10000, 20000]);
+        assert_eq!(vec![52166, 47830, 1938, 52013], report.counts);
+        assert_eq!(
+            vec![
+                (1, vec![2, 4, 3, 6]),
+                (20, vec![99, 97, 8, 103]),
+                (1000, vec![5204, 4792, 199, 5192]),
+                (10000, vec![52166, 47830, 1938, 52013]),
+            ],
+            report.checkpoints
+        );
+        assert_eq!(
+            "== After round 1 ==\n\
+             Monkey 0 inspected items 2 times.\n\
+             Monkey 1 inspected items 4 times.\n\
+             Monkey 2 inspected items 3 times.\n\
+             Monkey 3 inspected items 6 times.\n",
+            render_checkpoint(1, &report.checkpoints[0].1)
+        );
+    }
+
+    #[test]
+    fn skips_cycles() {
+        let monkey = |target| Monkey {
+            operation: Operation::parse("new = old + 1").unwrap(),
+            test: 2,
+            targets: (target, target),
+        };
+        let monkeys = vec![monkey(1), monkey(0)];
+        let items = vec![vec![Modular::new(0, 3)], vec![Modular::new(0, 3)]];
+        let report = simulate(items, &monkeys, 1_000_000_000, |n| n, &[7]);
+        assert_eq!(vec![1_999_999_999, 2_000_000_000], report.counts);
+        assert_eq!(vec![(7, vec![13, 14])], report.checkpoints);
+    }
+
+    #[test]
+    fn operations() {
+        let parse_op = |s| Operation::parse(s).unwrap();
+        assert_eq!(
+            Operation {
+                lhs: Operand::Num(3),
+                op: Operator::Mul,
+                rhs: Operand::Old
+            },
+            parse_op("new = 3 * old")
+        );
+        for s in ["new = old + old", "new = old * old", "new = 7 - old"] {
+            assert_eq!(s, parse_op(s).to_string());
+        }
+        assert_eq!(None, Operation::parse("new = old / 2"));
+        assert_eq!(None, Operation::parse("old + 1"));
+
+        assert_eq!(10, parse_op("new = old + old").eval(&5i64));
+        assert_eq!(-2, parse_op("new = 3 - old").eval(&5i64));
+        assert_eq!(
+            Modular::new(4, 7),
+            parse_op("new = old * old").eval(&Modular::new(9, 7))
+        );
+        assert_eq!(
+            Modular::new(5, 7),
+            parse_op("new = 3 - old").eval(&Modular::new(5, 7))
+        );
+    }
+
+    #[test]
+    fn parser() {
+        let lines = |s: &str| s.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+        let input = "
+
+Monkey 1:
+  Starting items:
+  Operation: new = 2 * old
+  Test: divisible by 5
+    If false: throw to monkey 0
+    If true: throw to monkey 1
+
+
+Monkey 0:
+  Starting items: 3, 4
+  Operation: new = old + old
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1";
+        let (items, monkeys) = parse(lines(input)).unwrap();
+        assert_eq!(vec![vec![3, 4], vec![]], items);
+        assert_eq!((1, 0), monkeys[1].targets);
+        assert_eq!("new = old + old", monkeys[0].operation.to_string());
+
+        assert_eq!(
+            Err(ParseError::UnknownTarget { line: 7, target: 2 }),
+            parse(lines(&input.replace("monkey 0", "monkey 2")))
+        );
+        assert_eq!(
+            Err(ParseError::MissingMonkey { monkey: 0 }),
+            parse(lines(&input.replace("Monkey 0:", "Monkey 2:")))
+        );
+        assert_eq!(
+            Err(ParseError::DuplicateMonkey {
+                line: 11,
+                monkey: 1
+            }),
+            parse(lines(&input.replace("Monkey 0:", "Monkey 1:")))
+        );
+        assert_eq!(
+            Err(ParseError::Malformed {
+                line: 13,
+                text: "Operation: new = old ^ 2".to_string()
+            }),
+            parse(lines(&input.replace("new = old + old", "new = old ^ 2")))
+        );
+        assert_eq!(
+            Err(ParseError::MissingField {
+                line: 3,
+                field: "Test"
+            }),
+            parse(lines(&input.replace("  Test: divisible by 5\n", "")))
+        );
     }
 }
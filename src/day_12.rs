@@ -2,54 +2,162 @@ use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::VecDeque;
 
-use crate::grid::Grid;
+use crate::grid::{heat, Grid};
 
 type Input = ((usize, usize), (usize, usize), Grid<i32>);
+type Pos = (usize, usize);
 
-fn one_impl((start, end, grid): &Input) -> usize {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Climb {
+    up: i32,
+    down: i32,
+}
+
+impl Climb {
+    fn standard() -> Climb {
+        Climb {
+            up: 1,
+            down: i32::MAX,
+        }
+    }
+
+    fn allows(&self, from: i32, to: i32) -> bool {
+        to - from <= self.up && from - to <= self.down
+    }
+}
+
+fn route(grid: &Grid<i32>, sources: &[Pos], end: Pos, climb: Climb) -> Option<Vec<Pos>> {
+    let mut came_from: Grid<Option<Pos>> = grid.map(|_| None);
     let mut queue = VecDeque::new();
-    let mut visited = HashSet::new();
-    queue.push_back((*start, 0));
-    while let Some((pos, n)) = queue.pop_front() {
-        if pos == *end {
-            return n;
+    for &pos in sources {
+        if came_from.get(pos.0, pos.1)?.is_none() {
+            came_from.set(pos.0, pos.1, Some(pos));
+            queue.push_back(pos);
         }
-        if visited.contains(&pos) {
-            continue;
+    }
+    while let Some(pos) = queue.pop_front() {
+        if pos == end {
+            let mut path = vec![pos];
+            let mut cur = pos;
+            while let Some(prev) = came_from.get(cur.0, cur.1).copied().flatten() {
+                if prev == cur {
+                    break;
+                }
+                path.push(prev);
+                cur = prev;
+            }
+            path.reverse();
+            return Some(path);
         }
-        visited.insert(pos);
         let cur = *grid.get(pos.0, pos.1).unwrap();
         for (nx, ny) in grid.nbors(pos.0, pos.1) {
-            if grid.get(nx, ny).unwrap() - cur <= 1 {
-                queue.push_back(((nx, ny), n + 1));
+            if came_from.get(nx, ny).unwrap().is_none()
+                && climb.allows(cur, *grid.get(nx, ny).unwrap())
+            {
+                came_from.set(nx, ny, Some(pos));
+                queue.push_back((nx, ny));
             }
         }
     }
-    unreachable!()
+    None
 }
 
-fn two_impl((_start, end, grid): &Input) -> usize {
+fn distance_field(grid: &Grid<i32>, end: Pos, climb: Climb) -> Grid<Option<u32>> {
+    let mut field = grid.map(|_| None);
     let mut queue = VecDeque::new();
-    let mut visited = HashSet::new();
-    queue.push_back((*end, 0));
-    while let Some((pos, n)) = queue.pop_front() {
+    if field.set(end.0, end.1, Some(0)).is_some() {
+        queue.push_back(end);
+    }
+    while let Some(pos) = queue.pop_front() {
         let cur = *grid.get(pos.0, pos.1).unwrap();
-        if cur == 0 {
-            return n;
-        }
-        if visited.contains(&pos) {
-            continue;
-        }
-        visited.insert(pos);
+        let n = field.get(pos.0, pos.1).copied().flatten().unwrap();
         for (nx, ny) in grid.nbors(pos.0, pos.1) {
-            if cur - grid.get(nx, ny).unwrap() <= 1 {
-                queue.push_back(((nx, ny), n + 1));
+            if field.get(nx, ny).unwrap().is_none() && climb.allows(*grid.get(nx, ny).unwrap(), cur)
+            {
+                field.set(nx, ny, Some(n + 1));
+                queue.push_back((nx, ny));
             }
         }
     }
-    unreachable!()
+    field
+}
+
+fn best_start(field: &Grid<Option<u32>>, candidates: &[Pos]) -> Option<(Pos, u32)> {
+    candidates
+        .iter()
+        .filter_map(|&(x, y)| field.get(x, y).copied().flatten().map(|n| ((x, y), n)))
+        .min_by_key(|(_, n)| *n)
+}
+
+#[allow(dead_code)]
+fn distance_ppm(field: &Grid<Option<u32>>) -> String {
+    let max = field.iter().flatten().copied().max().unwrap_or(0) as u64;
+    field.to_ppm(|n| match n {
+        Some(n) => heat(max - *n as u64, max),
+        None => (0, 0, 64),
+    })
+}
+
+#[allow(dead_code)]
+fn export_distances((_start, end, grid): &Input, path: &str) -> Result<(), std::io::Error> {
+    let field = distance_field(grid, *end, Climb::standard());
+    std::fs::write(path, distance_ppm(&field))
+}
+
+fn lowest(grid: &Grid<i32>) -> Vec<Pos> {
+    let mut lowest = Vec::new();
+    for y in 0..grid.col_size() {
+        for x in 0..grid.row_size() {
+            if *grid.get(x, y).unwrap() == 0 {
+                lowest.push((x, y));
+            }
+        }
+    }
+    lowest
+}
+
+#[allow(dead_code)]
+fn one_path((start, end, grid): &Input, climb: Climb) -> Option<Vec<Pos>> {
+    route(grid, &[*start], *end, climb)
+}
+
+#[allow(dead_code)]
+fn two_path((_start, end, grid): &Input, climb: Climb) -> Option<Vec<Pos>> {
+    route(grid, &lowest(grid), *end, climb)
+}
+
+#[allow(dead_code)]
+fn render_path(grid: &Grid<i32>, path: &[Pos]) -> String {
+    let mut overlay = grid.map(|_| '.');
+    for step in path.windows(2) {
+        let ((x, y), (nx, ny)) = (step[0], step[1]);
+        let arrow = if nx > x {
+            '>'
+        } else if nx < x {
+            '<'
+        } else if ny > y {
+            'v'
+        } else {
+            '^'
+        };
+        overlay.set(x, y, arrow);
+    }
+    if let Some(&(x, y)) = path.last() {
+        overlay.set(x, y, 'E');
+    }
+    overlay.to_string()
+}
+
+fn one_impl((start, end, grid): &Input) -> Option<u32> {
+    let field = distance_field(grid, *end, Climb::standard());
+    best_start(&field, &[*start]).map(|(_, n)| n)
+}
+
+fn two_impl((_start, end, grid): &Input) -> Option<u32> {
+    let field = distance_field(grid, *end, Climb::standard());
+    best_start(&field, &lowest(grid)).map(|(_, n)| n)
 }
 
 fn parse(reader: Vec<String>) -> Input {
@@ -78,14 +186,25 @@ pub fn twelve() -> Result<(), std::io::Error> {
     let file = File::open("input/12_input")?;
     let reader = BufReader::new(file);
     let input = parse(reader.lines().map(|t| t.unwrap()).collect());
-    println!("Day 12 part 1: {}", one_impl(&input));
-    println!("Day 12 part 2: {}", two_impl(&input));
+    let unreachable = || std::io::Error::new(std::io::ErrorKind::InvalidData, "no route to E");
+    println!(
+        "Day 12 part 1: {}",
+        one_impl(&input).ok_or_else(unreachable)?
+    );
+    println!(
+        "Day 12 part 2: {}",
+        two_impl(&input).ok_or_else(unreachable)?
+    );
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_12::{one_impl, parse, two_impl};
+    use crate::day_12::{
+        best_start, distance_field, distance_ppm, lowest, one_impl, one_path, parse, render_path,
+        two_impl, two_path, Climb,
+    };
+    use crate::grid::Grid;
 
     #[test]
     fn it_works() {
@@ -100,7 +219,56 @@ abdefghi
         .lines()
         .collect::<Vec<_>>();
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
-        assert_eq!(31, one_impl(&parsed));
-        assert_eq!(29, two_impl(&parsed));
+        assert_eq!(Some(31), one_impl(&parsed));
+        assert_eq!(Some(29), two_impl(&parsed));
+    }
+
+    #[test]
+    fn paths() {
+        let input = "
+Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi
+"
+        .trim()
+        .lines()
+        .collect::<Vec<_>>();
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let path = one_path(&parsed, Climb::standard()).unwrap();
+        assert_eq!((0, 0), path[0]);
+        assert_eq!((5, 2), *path.last().unwrap());
+        assert_eq!(
+            ">>vv<<<<\n..vvv<<^\n..vv>E^^\n..v>>>^^\n..>>>>>^\n",
+            render_path(&parsed.2, &path)
+        );
+        let path = two_path(&parsed, Climb::standard()).unwrap();
+        assert_eq!(0, *parsed.2.get(path[0].0, path[0].1).unwrap());
+        assert_eq!(30, path.len());
+
+        let steps = |climb| one_path(&parsed, climb).map(|p| p.len() - 1);
+        assert_eq!(Some(31), steps(Climb { up: 1, down: 1 }));
+        assert_eq!(None, steps(Climb { up: 0, down: 25 }));
+        assert_eq!(Some(7), steps(Climb { up: 25, down: 0 }));
+    }
+
+    #[test]
+    fn distances() {
+        let input = ["Sbxy", "zzyE", "aaaa"];
+        let (start, end, grid) = parse(input.iter().map(|s| s.to_string()).collect());
+        let field = distance_field(&grid, end, Climb::standard());
+        assert_eq!(
+            Grid::new(vec![
+                vec![None, None, Some(2), Some(1)],
+                vec![Some(3), Some(2), Some(1), Some(0)],
+                vec![None, None, None, None],
+            ]),
+            field
+        );
+        assert_eq!(None, best_start(&field, &[start]));
+        assert_eq!(None, best_start(&field, &lowest(&grid)));
+        assert_eq!(Some(((3, 0), 1)), best_start(&field, &[(0, 1), (3, 0)]));
+        assert!(distance_ppm(&field).starts_with("P3\n4 3\n255\n0 0 64 0 0 64 255 0 0 255 255 0\n"));
     }
 }
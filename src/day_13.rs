@@ -1,39 +1,9 @@
 use std::cmp::Ordering;
+use std::fmt;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::iter::Peekable;
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-enum Token {
-    Num(i32),
-    Op,
-    Cl,
-}
-
-fn tokenize(s: &str) -> Vec<Token> {
-    let mut out = Vec::new();
-    let mut iter = s.chars().peekable();
-    while let Some(c) = iter.next() {
-        if c == ',' {
-            continue;
-        } else if c == '[' {
-            out.push(Token::Op);
-        } else if c == ']' {
-            out.push(Token::Cl);
-        } else {
-            let mut num = c.to_string();
-            while iter.peek().unwrap().is_ascii_digit() {
-                num.push(iter.next().unwrap());
-            }
-            if iter.peek() == Some(&',') {
-                iter.next();
-            }
-            out.push(Token::Num(num.parse().unwrap()));
-        }
-    }
-    out
-}
+use std::io::{self, Write};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum Data {
@@ -58,22 +28,324 @@ impl PartialOrd for Data {
     }
 }
 
-fn recur<I>(iter: &mut Peekable<I>) -> Data
-where
-    I: Iterator<Item = Token>,
-{
-    match iter.next().unwrap() {
-        Token::Num(n) => Data::Num(n),
-        Token::Op => {
-            let mut vec = Vec::new();
-            while iter.peek().unwrap() != &Token::Cl {
-                vec.push(recur(iter));
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PacketError {
+    Unexpected {
+        line: usize,
+        column: usize,
+        expected: &'static str,
+        found: Option<char>,
+    },
+    BadNumber {
+        line: usize,
+        column: usize,
+        text: String,
+    },
+    BadPair {
+        line: usize,
+        count: usize,
+    },
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketError::Unexpected {
+                line,
+                column,
+                expected,
+                found,
+            } => match found {
+                Some(c) => write!(
+                    f,
+                    "line {}, column {}: expected {}, found '{}'",
+                    line, column, expected, c
+                ),
+                None => write!(
+                    f,
+                    "line {}, column {}: expected {}, found end of line",
+                    line, column, expected
+                ),
+            },
+            PacketError::BadNumber { line, column, text } => {
+                write!(f, "line {}, column {}: bad number {}", line, column, text)
+            }
+            PacketError::BadPair { line, count } => {
+                write!(
+                    f,
+                    "line {}: expected a pair of packets, found {}",
+                    line, count
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self
+            .chars
+            .get(self.pos)
+            .filter(|c| c.is_whitespace())
+            .is_some()
+        {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, expected: &'static str) -> PacketError {
+        PacketError::Unexpected {
+            line: self.line,
+            column: self.pos + 1,
+            expected,
+            found: self.chars.get(self.pos).copied(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Data, PacketError> {
+        match self.peek() {
+            Some('[') => self.list(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("'[' or a number")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Data, PacketError> {
+        self.pos += 1;
+        let mut vec = Vec::new();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Data::List(vec));
+        }
+        loop {
+            vec.push(self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Data::List(vec));
+                }
+                _ => return Err(self.error("',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Data, PacketError> {
+        let start = self.pos;
+        if self.chars[self.pos] == '-' {
+            self.pos += 1;
+        }
+        let digits = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .filter(|c| c.is_ascii_digit())
+            .is_some()
+        {
+            self.pos += 1;
+        }
+        if self.pos == digits {
+            return Err(self.error("a digit"));
+        }
+        let text = self.chars[start..self.pos].iter().collect::<String>();
+        text.parse()
+            .map(Data::Num)
+            .map_err(|_| PacketError::BadNumber {
+                line: self.line,
+                column: start + 1,
+                text,
+            })
+    }
+}
+
+impl Data {
+    fn parse(s: &str, line: usize) -> Result<Data, PacketError> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+            line,
+        };
+        let data = parser.value()?;
+        match parser.peek() {
+            None => Ok(data),
+            Some(_) => Err(parser.error("end of line")),
+        }
+    }
+
+    fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.pretty_(0, &mut out);
+        out
+    }
+
+    fn pretty_(&self, depth: usize, out: &mut String) {
+        match self {
+            Data::Num(n) => out.push_str(&n.to_string()),
+            Data::List(l) if l.is_empty() => out.push_str("[]"),
+            Data::List(l) => {
+                out.push_str("[\n");
+                for (i, d) in l.iter().enumerate() {
+                    out.push_str(&"  ".repeat(depth + 1));
+                    d.pretty_(depth + 1, out);
+                    if i + 1 < l.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push(']');
+            }
+        }
+    }
+}
+
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Data::Num(n) => write!(f, "{}", n),
+            Data::List(l) => {
+                write!(f, "[")?;
+                for (i, d) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", d)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Step {
+    Compare {
+        left: Data,
+        right: Data,
+        steps: Vec<Step>,
+    },
+    ConvertLeft(Data),
+    ConvertRight(Data),
+    LeftSmaller,
+    RightSmaller,
+    LeftRanOut,
+    RightRanOut,
+}
+
+impl Step {
+    fn render(&self, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        match self {
+            Step::Compare { left, right, steps } => {
+                out.push_str(&format!("- Compare {} vs {}\n", left, right));
+                for step in steps {
+                    step.render(depth + 1, out);
+                }
+                return;
+            }
+            Step::ConvertLeft(d) => out.push_str(&format!(
+                "- Mixed types; convert left to {} and retry comparison",
+                d
+            )),
+            Step::ConvertRight(d) => out.push_str(&format!(
+                "- Mixed types; convert right to {} and retry comparison",
+                d
+            )),
+            Step::LeftSmaller => {
+                out.push_str("- Left side is smaller, so inputs are in the right order")
+            }
+            Step::RightSmaller => {
+                out.push_str("- Right side is smaller, so inputs are not in the right order")
+            }
+            Step::LeftRanOut => {
+                out.push_str("- Left side ran out of items, so inputs are in the right order")
+            }
+            Step::RightRanOut => {
+                out.push_str("- Right side ran out of items, so inputs are not in the right order")
+            }
+        }
+        out.push('\n');
+    }
+}
+
+fn explain_(left: &Data, right: &Data, out: &mut Vec<Step>) -> Ordering {
+    let mut steps = Vec::new();
+    let ord = match (left, right) {
+        (Data::Num(n1), Data::Num(n2)) => {
+            let ord = n1.cmp(n2);
+            match ord {
+                Ordering::Less => steps.push(Step::LeftSmaller),
+                Ordering::Greater => steps.push(Step::RightSmaller),
+                Ordering::Equal => {}
             }
-            assert_eq!(Token::Cl, iter.next().unwrap());
-            Data::List(vec)
+            ord
+        }
+        (Data::List(l1), Data::List(l2)) => {
+            let mut ord = Ordering::Equal;
+            for i in 0.. {
+                match (l1.get(i), l2.get(i)) {
+                    (None, None) => break,
+                    (None, Some(_)) => {
+                        steps.push(Step::LeftRanOut);
+                        ord = Ordering::Less;
+                    }
+                    (Some(_), None) => {
+                        steps.push(Step::RightRanOut);
+                        ord = Ordering::Greater;
+                    }
+                    (Some(d1), Some(d2)) => ord = explain_(d1, d2, &mut steps),
+                }
+                if ord != Ordering::Equal {
+                    break;
+                }
+            }
+            ord
+        }
+        (Data::Num(n1), list) => {
+            let converted = Data::List(vec![Data::Num(*n1)]);
+            steps.push(Step::ConvertLeft(converted.clone()));
+            explain_(&converted, list, &mut steps)
         }
-        Token::Cl => unreachable!(),
+        (list, Data::Num(n2)) => {
+            let converted = Data::List(vec![Data::Num(*n2)]);
+            steps.push(Step::ConvertRight(converted.clone()));
+            explain_(list, &converted, &mut steps)
+        }
+    };
+    out.push(Step::Compare {
+        left: left.clone(),
+        right: right.clone(),
+        steps,
+    });
+    ord
+}
+
+fn explain(left: &Data, right: &Data) -> (Ordering, Step) {
+    let mut steps = Vec::new();
+    let ord = explain_(left, right, &mut steps);
+    (ord, steps.pop().unwrap())
+}
+
+#[allow(dead_code)]
+fn explain_pairs(input: &Input) -> String {
+    let mut out = String::new();
+    for (i, (first, second)) in input.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("== Pair {} ==\n", i + 1));
+        explain(first, second).1.render(0, &mut out);
     }
+    out
 }
 
 type Input = Vec<(Data, Data)>;
@@ -88,27 +360,73 @@ fn one_impl(input: &Input) -> usize {
     correct
 }
 
-fn two_impl(input: &Input) -> usize {
-    let marker1 = Data::List(vec![Data::List(vec![Data::Num(2)])]);
-    let marker2 = Data::List(vec![Data::List(vec![Data::Num(6)])]);
-    let mut list = input
+fn packets(input: &Input) -> impl Iterator<Item = &Data> {
+    input.iter().flat_map(|(f, s)| [f, s])
+}
+
+fn divider_indices(input: &Input, dividers: &[Data]) -> Vec<usize> {
+    dividers
         .iter()
-        .flat_map(|(f, s)| [f.clone(), s.clone()])
-        .collect::<Vec<Data>>();
-    list.push(marker1.clone());
-    list.push(marker2.clone());
+        .map(|divider| {
+            packets(input)
+                .chain(dividers)
+                .filter(|p| *p < divider)
+                .count()
+                + 1
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+fn write_sorted<W: Write>(input: &Input, dividers: &[Data], out: &mut W) -> io::Result<()> {
+    let mut list = packets(input).chain(dividers).collect::<Vec<_>>();
     list.sort();
-    (list.iter().position(|m| m == &marker1).unwrap() + 1)
-        * (list.iter().position(|m| m == &marker2).unwrap() + 1)
+    for packet in list {
+        writeln!(out, "{}", packet)?;
+    }
+    Ok(())
 }
 
-fn parse(reader: Vec<String>) -> Input {
-    reader
-        .chunks(3)
-        .map(|chunk| {
-            let first = recur(&mut tokenize(&chunk[0]).into_iter().peekable());
-            let second = recur(&mut tokenize(&chunk[1]).into_iter().peekable());
-            (first, second)
+fn two_impl(input: &Input) -> usize {
+    let dividers = [
+        Data::List(vec![Data::List(vec![Data::Num(2)])]),
+        Data::List(vec![Data::List(vec![Data::Num(6)])]),
+    ];
+    divider_indices(input, &dividers).iter().product()
+}
+
+#[allow(dead_code)]
+fn pretty_print(input: &Input) -> String {
+    input
+        .iter()
+        .map(|(first, second)| format!("{}\n{}\n", first.pretty(), second.pretty()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse(reader: Vec<String>) -> Result<Input, PacketError> {
+    let mut blocks: Vec<Vec<(usize, Data)>> = vec![Vec::new()];
+    for (i, s) in reader.iter().enumerate() {
+        if s.trim().is_empty() {
+            if !blocks.last().unwrap().is_empty() {
+                blocks.push(Vec::new());
+            }
+        } else {
+            let packet = Data::parse(s, i + 1)?;
+            blocks.last_mut().unwrap().push((i + 1, packet));
+        }
+    }
+    blocks
+        .into_iter()
+        .filter(|block| !block.is_empty())
+        .map(|block| {
+            let line = block[0].0;
+            let count = block.len();
+            let mut packets = block.into_iter().map(|(_, d)| d);
+            match (packets.next(), packets.next(), packets.next()) {
+                (Some(first), Some(second), None) => Ok((first, second)),
+                _ => Err(PacketError::BadPair { line, count }),
+            }
         })
         .collect()
 }
@@ -116,7 +434,8 @@ fn parse(reader: Vec<String>) -> Input {
 pub fn thirteen() -> Result<(), std::io::Error> {
     let file = File::open("input/13_input")?;
     let reader = BufReader::new(file);
-    let input = parse(reader.lines().map(|t| t.unwrap()).collect());
+    let input = parse(reader.lines().map(|t| t.unwrap()).collect())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     println!("Day 13 part 1: {}", one_impl(&input));
     println!("Day 13 part 2: {}", two_impl(&input));
     Ok(())
@@ -124,7 +443,10 @@ pub fn thirteen() -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::day_13::{one_impl, parse, two_impl};
+    use crate::day_13::{
+        divider_indices, explain, explain_pairs, one_impl, parse, pretty_print, two_impl,
+        write_sorted, Data, PacketError, Step,
+    };
 
     #[test]
     fn it_works() {
@@ -156,8 +478,186 @@ mod tests {
         .trim()
         .lines()
         .collect::<Vec<_>>();
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(13, one_impl(&parsed));
         assert_eq!(140, two_impl(&parsed));
     }
+
+    #[test]
+    fn parser() {
+        for s in [
+            "[]",
+            "[[[]]]",
+            "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+            "[-3,10,[-12]]",
+            "7",
+        ] {
+            assert_eq!(s, Data::parse(s, 1).unwrap().to_string());
+        }
+        assert_eq!(
+            Data::parse("[1,[-2,30]]", 1),
+            Data::parse(" [ 1 , [ -2, 30 ] ] ", 1)
+        );
+        assert_eq!(
+            "[\n  1,\n  [\n    2\n  ],\n  []\n]",
+            Data::parse("[1,[2],[]]", 1).unwrap().pretty()
+        );
+
+        let unexpected = |column, expected, found| PacketError::Unexpected {
+            line: 4,
+            column,
+            expected,
+            found,
+        };
+        let err = |s| Data::parse(s, 4).unwrap_err();
+        assert_eq!(unexpected(5, "',' or ']'", None), err("[1,2"));
+        assert_eq!(unexpected(4, "end of line", Some(']')), err("[1]]"));
+        assert_eq!(unexpected(4, "'[' or a number", Some(']')), err("[1,]"));
+        assert_eq!(unexpected(3, "a digit", Some('x')), err("[-x]"));
+        assert_eq!(unexpected(1, "'[' or a number", None), err(""));
+        assert_eq!(
+            PacketError::BadNumber {
+                line: 4,
+                column: 2,
+                text: "99999999999".to_string()
+            },
+            err("[99999999999]")
+        );
+        assert_eq!(
+            "line 4, column 5: expected ',' or ']', found end of line",
+            err("[1,2").to_string()
+        );
+
+        let parse_lines = |lines: &[&str]| parse(lines.iter().map(|s| s.to_string()).collect());
+        let lines = ["[1]", "[2]", "", "", "[3]"];
+        assert_eq!(
+            Err(PacketError::BadPair { line: 5, count: 1 }),
+            parse_lines(&lines)
+        );
+        let lines = ["[1]", "[2]", "", "[3]", "", "[4]", "[5]", "", "[6]", "[7]"];
+        assert_eq!(
+            Err(PacketError::BadPair { line: 4, count: 1 }),
+            parse_lines(&lines)
+        );
+        let lines = ["[1]", "[2]", "[3]", "", "[4]"];
+        let err = parse_lines(&lines).unwrap_err();
+        assert_eq!(PacketError::BadPair { line: 1, count: 3 }, err);
+        assert_eq!(
+            "line 1: expected a pair of packets, found 3",
+            err.to_string()
+        );
+        assert_eq!(
+            2,
+            parse_lines(&["", "[1]", "[2]", "", "", "[3]", "[4]", ""])
+                .unwrap()
+                .len()
+        );
+        let parsed = parse(lines[..2].iter().map(|s| s.to_string()).collect()).unwrap();
+        assert_eq!("[\n  1\n]\n[\n  2\n]\n", pretty_print(&parsed));
+    }
+
+    #[test]
+    fn explains() {
+        let input = "
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+"
+        .trim()
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let parsed = parse(input).unwrap();
+        let expected = "
+== Pair 1 ==
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+
+== Pair 2 ==
+- Compare [9] vs [[8,7,6]]
+  - Compare 9 vs [8,7,6]
+    - Mixed types; convert left to [9] and retry comparison
+    - Compare [9] vs [8,7,6]
+      - Compare 9 vs 8
+        - Right side is smaller, so inputs are not in the right order
+
+== Pair 3 ==
+- Compare [[4,4],4,4] vs [[4,4],4,4,4]
+  - Compare [4,4] vs [4,4]
+    - Compare 4 vs 4
+    - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Left side ran out of items, so inputs are in the right order
+
+== Pair 4 ==
+- Compare [7,7,7,7] vs [7,7,7]
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Right side ran out of items, so inputs are not in the right order
+";
+        assert_eq!(&expected[1..], explain_pairs(&parsed));
+        for (first, second) in &parsed {
+            assert_eq!(first.cmp(second), explain(first, second).0);
+        }
+
+        let same = Data::parse("[[]]", 1).unwrap();
+        let (ord, step) = explain(&same, &same);
+        assert_eq!(std::cmp::Ordering::Equal, ord);
+        assert_eq!(
+            Step::Compare {
+                left: same.clone(),
+                right: same.clone(),
+                steps: vec![Step::Compare {
+                    left: Data::List(vec![]),
+                    right: Data::List(vec![]),
+                    steps: vec![]
+                }]
+            },
+            step
+        );
+    }
+
+    #[test]
+    fn dividers() {
+        let lines = [
+            "[1,1,3,1,1]",
+            "[1,1,5,1,1]",
+            "",
+            "[[1],[2,3,4]]",
+            "[[1],4]",
+            "",
+            "[]",
+            "[3]",
+        ];
+        let parsed = parse(lines.iter().map(|s| s.to_string()).collect()).unwrap();
+        let dividers = ["[[2]]", "[[6]]", "[0]", "[1,1,5,1,1]"]
+            .iter()
+            .map(|s| Data::parse(s, 1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![8, 10, 2, 4], divider_indices(&parsed, &dividers));
+        assert_eq!(Vec::<usize>::new(), divider_indices(&parsed, &[]));
+
+        let mut out = Vec::new();
+        write_sorted(&parsed, &dividers[..2], &mut out).unwrap();
+        assert_eq!(
+            "[]\n[1,1,3,1,1]\n[1,1,5,1,1]\n[[1],[2,3,4]]\n[[1],4]\n[[2]]\n[3]\n[[6]]\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
 }
@@ -1,116 +1,216 @@
 use crate::grid::Grid;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
 type Input = Vec<Vec<(i32, i32)>>;
+type Pos = (i32, i32);
 
-fn one_impl(input: &Input) -> usize {
-    let max_x = *input
-        .iter()
-        .flat_map(|row| row.iter().map(|(x, _)| x))
-        .max()
-        .unwrap()
-        + 1;
-    let max_y = *input
-        .iter()
-        .flat_map(|row| row.iter().map(|(_, y)| y))
-        .max()
-        .unwrap()
-        + 1;
-
-    let mut grid = Grid::new_with(max_x as usize, max_y as usize, |_, _| '.');
-    for lineset in input {
-        let mut start = lineset[0];
-        for line in lineset {
-            let dx = (line.0 - start.0).signum();
-            let dy = (line.1 - start.1).signum();
-            grid.set(start.0 as usize, start.1 as usize, '#');
-            while *line != start {
-                start.0 += dx;
-                start.1 += dy;
-                grid.set(start.0 as usize, start.1 as usize, '#');
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Rock,
+    Sand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bottom {
+    Abyss,
+    Floor(i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Grain {
+    Rest(Pos),
+    Abyss,
+    Blocked,
+}
+
+struct Cave {
+    cells: HashMap<Pos, Cell>,
+    max_y: i32,
+    bottom: Bottom,
+    sources: Vec<(Pos, Vec<Pos>)>,
+    last: Vec<Pos>,
+}
+
+impl Cave {
+    fn new(input: &Input, bottom: Bottom, sources: &[Pos]) -> Cave {
+        let mut cells = HashMap::new();
+        for lineset in input {
+            let mut start = lineset[0];
+            for line in lineset {
+                let dx = (line.0 - start.0).signum();
+                let dy = (line.1 - start.1).signum();
+                cells.insert(start, Cell::Rock);
+                while *line != start {
+                    start.0 += dx;
+                    start.1 += dy;
+                    cells.insert(start, Cell::Rock);
+                }
             }
         }
+        let max_y = cells.keys().map(|(_, y)| *y).max().unwrap_or(0);
+        Cave {
+            cells,
+            max_y,
+            bottom,
+            sources: sources.iter().map(|s| (*s, Vec::new())).collect(),
+            last: Vec::new(),
+        }
     }
 
-    let mut units = 0;
-    'outer: loop {
-        units += 1;
-        let mut unit = (500, 0);
-        loop {
-            if grid.get(unit.0, unit.1 + 1).is_none() {
-                break 'outer;
-            }
-            if grid.get(unit.0, unit.1 + 1) == Some(&'.') {
-                unit = (unit.0, unit.1 + 1);
-            } else if grid.get(unit.0 - 1, unit.1 + 1) == Some(&'.') {
-                unit = (unit.0 - 1, unit.1 + 1);
-            } else if grid.get(unit.0 + 1, unit.1 + 1) == Some(&'.') {
-                unit = (unit.0 + 1, unit.1 + 1);
-            } else {
-                grid.set(unit.0, unit.1, '+');
+    fn free(&self, pos: Pos) -> bool {
+        match self.bottom {
+            Bottom::Floor(y) if pos.1 >= y => false,
+            _ => !self.cells.contains_key(&pos),
+        }
+    }
+
+    fn drop_grain(&mut self, source: usize) -> Grain {
+        let (start, mut path) = (
+            self.sources[source].0,
+            std::mem::take(&mut self.sources[source].1),
+        );
+        while let Some(&last) = path.last() {
+            if self.free(last) {
                 break;
             }
+            path.pop();
+        }
+        if path.is_empty() {
+            if !self.free(start) {
+                return Grain::Blocked;
+            }
+            path.push(start);
         }
+        let grain = loop {
+            let (x, y) = *path.last().unwrap();
+            if self.bottom == Bottom::Abyss && y > self.max_y {
+                break Grain::Abyss;
+            }
+            match [(x, y + 1), (x - 1, y + 1), (x + 1, y + 1)]
+                .iter()
+                .find(|next| self.free(**next))
+            {
+                Some(next) => path.push(*next),
+                None => break Grain::Rest((x, y)),
+            }
+        };
+        self.last = path.clone();
+        if let Grain::Rest(pos) = grain {
+            self.cells.insert(pos, Cell::Sand);
+            path.pop();
+        }
+        self.sources[source].1 = path;
+        grain
     }
 
-    units - 1
-}
+    fn run(&mut self, limit: usize) -> usize {
+        let mut grains = 0;
+        let mut active = (0..self.sources.len()).collect::<Vec<_>>();
+        while !active.is_empty() && grains < limit {
+            active.retain(|&source| {
+                if grains == limit {
+                    return true;
+                }
+                match self.drop_grain(source) {
+                    Grain::Rest(_) => {
+                        grains += 1;
+                        true
+                    }
+                    Grain::Abyss | Grain::Blocked => false,
+                }
+            });
+        }
+        grains
+    }
 
-fn two_impl(input: &Input) -> usize {
-    let max_y = *input
-        .iter()
-        .flat_map(|row| row.iter().map(|(_, y)| y))
-        .max()
-        .unwrap()
-        + 2;
-    let max_x = *input
-        .iter()
-        .flat_map(|row| row.iter().map(|(x, _)| x))
-        .max()
-        .unwrap()
-        + max_y;
-
-    let mut grid = Grid::new_with(max_x as usize, max_y as usize, |_, _| '.');
-    for lineset in input {
-        let mut start = lineset[0];
-        for line in lineset {
-            let dx = (line.0 - start.0).signum();
-            let dy = (line.1 - start.1).signum();
-            grid.set(start.0 as usize, start.1 as usize, '#');
-            while *line != start {
-                start.0 += dx;
-                start.1 += dy;
-                grid.set(start.0 as usize, start.1 as usize, '#');
-            }
+    fn fill(&mut self) -> usize {
+        self.run(usize::MAX)
+    }
+
+    fn bounds(&self, trail: bool) -> (Pos, Pos) {
+        let mut points = self
+            .cells
+            .keys()
+            .chain(self.sources.iter().map(|(s, _)| s))
+            .copied()
+            .collect::<Vec<_>>();
+        if trail {
+            points.extend(&self.last);
+        }
+        let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+        let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+        let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+        let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+        match self.bottom {
+            Bottom::Floor(y) => ((min_x - 1, min_y), (max_x + 1, y)),
+            Bottom::Abyss => ((min_x, min_y), (max_x, max_y)),
         }
     }
 
-    let mut units = 0;
-    loop {
-        units += 1;
-        let mut unit = (500, 0);
-        if grid.get(unit.0, unit.1) == Some(&'+') {
-            break;
+    fn render(&self, trail: bool) -> Grid<char> {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds(trail);
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        let mut grid = Grid::new_with(width, height, |x, y| {
+            let pos = (x as i32 + min_x, y as i32 + min_y);
+            match (self.cells.get(&pos), self.bottom) {
+                (Some(Cell::Rock), _) => '#',
+                (Some(Cell::Sand), _) => 'o',
+                (None, Bottom::Floor(floor)) if pos.1 == floor => '#',
+                (None, _) => '.',
+            }
+        });
+        let mut mark = |(x, y): Pos, c: char| {
+            let (x, y) = ((x - min_x) as usize, (y - min_y) as usize);
+            if grid.get(x, y) == Some(&'.') {
+                grid.set(x, y, c);
+            }
+        };
+        for (source, _) in &self.sources {
+            mark(*source, '+');
         }
-        loop {
-            if grid.get(unit.0, unit.1 + 1) == Some(&'.') {
-                unit = (unit.0, unit.1 + 1);
-            } else if grid.get(unit.0 - 1, unit.1 + 1) == Some(&'.') {
-                unit = (unit.0 - 1, unit.1 + 1);
-            } else if grid.legal(unit.0 as i32 + 1, unit.1 as i32 + 1)
-                && grid.get(unit.0 + 1, unit.1 + 1).unwrap_or(&'.') == &'.'
-            {
-                unit = (unit.0 + 1, unit.1 + 1);
-            } else {
-                grid.set(unit.0, unit.1, '+');
-                break;
+        if trail {
+            for pos in &self.last {
+                mark(*pos, '~');
             }
         }
+        grid
     }
+}
+
+#[allow(dead_code)]
+fn snapshot(cave: &mut Cave, grains: Option<usize>, trail: bool) -> String {
+    cave.run(grains.unwrap_or(usize::MAX));
+    cave.render(trail).to_string()
+}
+
+#[allow(dead_code)]
+fn cave_ppm(cave: &Cave, trail: bool) -> String {
+    cave.render(trail).to_ppm(|c| match c {
+        '#' => (128, 128, 128),
+        'o' => (230, 200, 90),
+        '~' => (60, 120, 255),
+        '+' => (255, 0, 0),
+        _ => (0, 0, 0),
+    })
+}
+
+#[allow(dead_code)]
+fn export_cave(cave: &Cave, trail: bool, path: &str) -> Result<(), std::io::Error> {
+    std::fs::write(path, cave_ppm(cave, trail))
+}
 
-    units - 1
+fn one_impl(input: &Input) -> usize {
+    Cave::new(input, Bottom::Abyss, &[(500, 0)]).fill()
+}
+
+fn two_impl(input: &Input) -> usize {
+    let mut cave = Cave::new(input, Bottom::Abyss, &[(500, 0)]);
+    cave.bottom = Bottom::Floor(cave.max_y + 2);
+    cave.fill()
 }
 
 fn parse(reader: Vec<String>) -> Input {
@@ -138,7 +238,10 @@ pub fn fourteen() -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::day_14::{one_impl, parse, two_impl};
+    use crate::day_14::{
+        cave_ppm, one_impl, parse, snapshot, two_impl, Bottom, Cave, Cell, Grain, Input,
+    };
+    use std::collections::HashSet;
 
     #[test]
     fn it_works() {
@@ -153,4 +256,170 @@ mod tests {
         assert_eq!(24, one_impl(&parsed));
         assert_eq!(93, two_impl(&parsed));
     }
+
+    #[test]
+    fn engine() {
+        let floor = |sources: &[(i32, i32)]| Cave::new(&vec![], Bottom::Floor(3), sources).fill();
+        assert_eq!(9, floor(&[(500, 0)]));
+        assert_eq!(18, floor(&[(500, 0), (510, 0)]));
+        assert_eq!(14, floor(&[(500, 0), (502, 0)]));
+        assert_eq!(0, Cave::new(&vec![], Bottom::Abyss, &[(500, 0)]).fill());
+
+        let input = parse(vec!["498,2 -> 502,2".to_string()]);
+        let mut cave = Cave::new(&input, Bottom::Abyss, &[(500, 0)]);
+        assert_eq!(Grain::Rest((500, 1)), cave.drop_grain(0));
+        assert_eq!(vec![(500, 0), (500, 1)], cave.last);
+        assert_eq!(Grain::Rest((499, 1)), cave.drop_grain(0));
+        assert_eq!(Grain::Rest((501, 1)), cave.drop_grain(0));
+        assert_eq!(Grain::Rest((500, 0)), cave.drop_grain(0));
+        assert_eq!(Grain::Blocked, cave.drop_grain(0));
+
+        let input = parse(vec!["499,2 -> 501,2".to_string()]);
+        let mut cave = Cave::new(&input, Bottom::Floor(4), &[(500, 0)]);
+        assert_eq!(12, cave.fill());
+        let mut cave = Cave::new(&input, Bottom::Abyss, &[(505, 0)]);
+        assert_eq!(Grain::Abyss, cave.drop_grain(0));
+        assert_eq!(vec![(505, 0), (505, 1), (505, 2), (505, 3)], cave.last);
+    }
+
+    #[test]
+    fn renders() {
+        let input = parse(vec![
+            "498,4 -> 498,6 -> 496,6".to_string(),
+            "503,4 -> 502,4 -> 502,9 -> 494,9".to_string(),
+        ]);
+        let mut cave = Cave::new(&input, Bottom::Abyss, &[(500, 0)]);
+        let expected = "
+......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+......o.#.
+....oooo#.
+#########.
+";
+        assert_eq!(&expected[1..], snapshot(&mut cave, Some(5), false));
+        let expected = "
+.......+...
+.......~...
+......~o...
+.....~ooo..
+....~#ooo##
+...~o#ooo#.
+..~###ooo#.
+..~..oooo#.
+.~o.ooooo#.
+~#########.
+~..........
+";
+        assert_eq!(&expected[1..], snapshot(&mut cave, None, true));
+
+        let mut cave = Cave::new(&input, Bottom::Floor(11), &[(500, 0)]);
+        let expected = "
+...........o...........
+..........ooo..........
+.........ooooo.........
+........ooooooo........
+.......oo#ooo##o.......
+......ooo#ooo#ooo......
+.....oo###ooo#oooo.....
+....oooo.oooo#ooooo....
+...oooooooooo#oooooo...
+..ooo#########ooooooo..
+.ooooo.......ooooooooo.
+#######################
+";
+        assert_eq!(&expected[1..], snapshot(&mut cave, None, true));
+        assert!(cave_ppm(&cave, true).starts_with("P3\n23 12\n255\n0 0 0 "));
+
+        let mut cave = Cave::new(&vec![], Bottom::Floor(2), &[(500, 0), (503, 0)]);
+        assert_eq!(
+            ".+..+.\n......\n######\n",
+            snapshot(&mut cave, Some(0), true)
+        );
+        assert_eq!(
+            "..+..+.\n.oo.oo.\n#######\n",
+            snapshot(&mut cave, Some(4), false)
+        );
+    }
+
+    fn brute_force(input: &Input, floor: i32, sources: &[(i32, i32)]) -> HashSet<(i32, i32)> {
+        let rocks = Cave::new(input, Bottom::Floor(floor), sources)
+            .cells
+            .into_keys()
+            .collect::<HashSet<_>>();
+        let blocked = |p: &(i32, i32), sand: &HashSet<(i32, i32)>| {
+            p.1 >= floor || rocks.contains(p) || sand.contains(p)
+        };
+        let mut sand = HashSet::new();
+        let mut active = sources.to_vec();
+        while !active.is_empty() {
+            active.retain(|&(x, y)| {
+                if blocked(&(x, y), &sand) {
+                    return false;
+                }
+                let (mut x, mut y) = (x, y);
+                while let Some(next) = [(x, y + 1), (x - 1, y + 1), (x + 1, y + 1)]
+                    .iter()
+                    .copied()
+                    .find(|p| !blocked(p, &sand))
+                {
+                    (x, y) = next;
+                }
+                sand.insert((x, y));
+                true
+            });
+        }
+        sand
+    }
+
+    #[test]
+    fn shared_paths() {
+        let input = parse(vec![
+            "499,4 -> 502,4".to_string(),
+            "498,5 -> 498,7".to_string(),
+        ]);
+        let sources = [(497, 2), (504, 2), (504, 2)];
+        let mut cave = Cave::new(&input, Bottom::Floor(12), &sources);
+        assert_eq!(151, cave.fill());
+        assert_eq!(151, brute_force(&input, 12, &sources).len());
+
+        let mut seed = 7u64;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as i32
+        };
+        for _ in 0..200 {
+            let input = (0..1 + next(3))
+                .map(|_| {
+                    let (x, y) = (490 + next(20), 3 + next(6));
+                    let end = if next(2) == 0 {
+                        (x + next(5), y)
+                    } else {
+                        (x, y + next(4))
+                    };
+                    format!("{},{} -> {},{}", x, y, end.0, end.1)
+                })
+                .collect();
+            let input = parse(input);
+            let sources = (0..1 + next(3))
+                .map(|_| (490 + next(20), next(3)))
+                .collect::<Vec<_>>();
+            let mut cave = Cave::new(&input, Bottom::Floor(14), &sources);
+            let expected = brute_force(&input, 14, &sources);
+            assert_eq!(expected.len(), cave.fill(), "{:?} {:?}", input, sources);
+            let sand = cave
+                .cells
+                .iter()
+                .filter(|(_, c)| **c == Cell::Sand)
+                .map(|(p, _)| *p)
+                .collect::<HashSet<_>>();
+            assert_eq!(expected, sand);
+        }
+    }
 }
@@ -7,78 +7,182 @@ use scanf::sscanf;
 
 type Input = (HashSet<((i32, i32), (i32, i32))>, HashSet<(i32, i32)>);
 
-fn manhattan(p1: (i32, i32), p2: (i32, i32)) -> i32 {
-    (p1.0 - p2.0).abs() + (p1.1 - p2.1).abs()
+type Point = (i64, i64);
+
+fn manhattan(p1: Point, p2: Point) -> u64 {
+    p1.0.abs_diff(p2.0).saturating_add(p1.1.abs_diff(p2.1))
 }
 
-fn one_impl(y: i32, input: &Input) -> usize {
-    let mut covered = 0;
-    let min_x = input
-        .0
-        .iter()
-        .map(|(s, b)| s.0 - manhattan(*s, *b))
-        .min()
-        .unwrap();
-    let max_x = input
-        .0
-        .iter()
-        .map(|(s, b)| s.0 + manhattan(*s, *b))
-        .max()
-        .unwrap();
+struct SensorField {
+    sensors: Vec<(Point, u64)>,
+    beacons: HashSet<Point>,
+}
 
-    for x in min_x..max_x {
-        let mut c = '.';
-        for (sensor, closest_beacon) in &input.0 {
-            let d1 = manhattan(*sensor, *closest_beacon);
-            let d2 = manhattan(*sensor, (x, y));
-            if *sensor == (x, y) {
-                c = 'S';
-                break;
-            } else if *closest_beacon == (x, y) {
-                c = 'B';
+#[allow(dead_code)]
+impl SensorField {
+    fn new((sensors, beacons): &Input) -> SensorField {
+        let point = |(x, y): (i32, i32)| (x as i64, y as i64);
+        let mut sensors = sensors
+            .iter()
+            .map(|(s, b)| (point(*s), manhattan(point(*s), point(*b))))
+            .collect::<Vec<_>>();
+        sensors.sort_unstable();
+        SensorField {
+            sensors,
+            beacons: beacons.iter().map(|b| point(*b)).collect(),
+        }
+    }
+
+    fn covers(&self, p: Point) -> bool {
+        self.sensors.iter().any(|(s, r)| manhattan(*s, p) <= *r)
+    }
+
+    fn covering(&self, p: Point) -> Vec<Point> {
+        self.sensors
+            .iter()
+            .filter(|(s, r)| manhattan(*s, p) <= *r)
+            .map(|(s, _)| *s)
+            .collect()
+    }
+
+    fn row(&self, y: i64) -> Vec<(i64, i64)> {
+        let mut intervals = self
+            .sensors
+            .iter()
+            .filter_map(|(s, r)| {
+                let reach = r.checked_sub(s.1.abs_diff(y))? as i64;
+                Some((s.0 - reach, s.0 + reach))
+            })
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+        let mut merged: Vec<(i64, i64)> = Vec::new();
+        for (lo, hi) in intervals {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        merged
+    }
+
+    fn gaps(&self, y: i64, min_x: i64, max_x: i64) -> Vec<(i64, i64)> {
+        let mut gaps = Vec::new();
+        let mut cursor = min_x;
+        for (lo, hi) in self.row(y) {
+            if lo > max_x {
                 break;
             }
-            if d2 <= d1 {
-                c = '#';
+            if hi < cursor {
+                continue;
             }
+            if lo > cursor {
+                gaps.push((cursor, lo - 1));
+            }
+            cursor = hi + 1;
         }
-        if c == '#' {
-            covered += 1;
+        if cursor <= max_x {
+            gaps.push((cursor, max_x));
         }
+        gaps
     }
 
-    covered
-}
+    fn uncovered(&self, min: Point, max: Point) -> impl Iterator<Item = Point> + '_ {
+        (min.1..=max.1).flat_map(move |y| {
+            self.gaps(y, min.0, max.0)
+                .into_iter()
+                .flat_map(move |(lo, hi)| (lo..=hi).map(move |x| (x, y)))
+        })
+    }
 
-fn two_impl(max_x: i32, max_y: i32, input: &Input) -> usize {
-    for (sensor, closest_beacon) in &input.0 {
-        let d1 = manhattan(*sensor, *closest_beacon) + 1;
-        let positions = (0..=d1)
-            .flat_map(|d| {
-                [
-                    (sensor.0 + d, sensor.1 + (d1 - d)),
-                    (sensor.0 - d, sensor.1 + (d1 - d)),
-                    (sensor.0 + d, sensor.1 - (d1 - d)),
-                    (sensor.0 - d, sensor.1 - (d1 - d)),
-                ]
-            })
-            .collect::<HashSet<(i32, i32)>>();
-
-        'outer: for (x, y) in positions {
-            for (sensor, closest_beacon) in &input.0 {
-                let d1 = manhattan(*sensor, *closest_beacon);
-                let d2 = manhattan(*sensor, (x, y));
-                if d2 <= d1 {
-                    continue 'outer;
+    fn covered_area(&self, min: Point, max: Point) -> u128 {
+        let top = self.sensors.iter().map(|(s, r)| s.1 - *r as i64).min();
+        let bottom = self.sensors.iter().map(|(s, r)| s.1 + *r as i64).max();
+        let (top, bottom) = match (top, bottom) {
+            (Some(top), Some(bottom)) => (top.max(min.1), bottom.min(max.1)),
+            _ => return 0,
+        };
+        let mut area = 0;
+        for y in top..=bottom {
+            for (lo, hi) in self.row(y) {
+                let (lo, hi) = (lo.max(min.0), hi.min(max.0));
+                if lo <= hi {
+                    area += (hi - lo) as u128 + 1;
                 }
             }
-            if x >= 0 && x <= max_x && y >= 0 && y <= max_y {
-                return x as usize * 4000000 + y as usize;
+        }
+        area
+    }
+
+    fn boundaries(&self) -> (Vec<i128>, Vec<i128>) {
+        let mut us = Vec::new();
+        let mut vs = Vec::new();
+        for (s, r) in &self.sensors {
+            let (u, v, r) = ((s.0 + s.1) as i128, (s.0 - s.1) as i128, *r as i128 + 1);
+            us.extend([u - r, u + r]);
+            vs.extend([v - r, v + r]);
+        }
+        us.sort_unstable();
+        us.dedup();
+        vs.sort_unstable();
+        vs.dedup();
+        (us, vs)
+    }
+
+    fn find_beacon(&self, min: Point, max: Point) -> Option<Point> {
+        let (us, vs) = self.boundaries();
+        let (min_x, min_y, max_x, max_y) =
+            (min.0 as i128, min.1 as i128, max.0 as i128, max.1 as i128);
+        let mut candidates = vec![
+            (min_x, min_y),
+            (min_x, max_y),
+            (max_x, min_y),
+            (max_x, max_y),
+        ];
+        for &u in &us {
+            for &v in &vs {
+                if (u + v) % 2 == 0 {
+                    candidates.push(((u + v) / 2, (u - v) / 2));
+                } else {
+                    for (du, dv) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
+                        let (u, v) = (u + du, v + dv);
+                        candidates.push(((u + v) / 2, (u - v) / 2));
+                    }
+                }
             }
+            candidates.extend([(min_x, u - min_x), (max_x, u - max_x)]);
+            candidates.extend([(u - min_y, min_y), (u - max_y, max_y)]);
         }
+        for &v in &vs {
+            candidates.extend([(min_x, min_x - v), (max_x, max_x - v)]);
+            candidates.extend([(v + min_y, min_y), (v + max_y, max_y)]);
+        }
+        candidates
+            .into_iter()
+            .filter(|(x, y)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+            .map(|(x, y)| (x as i64, y as i64))
+            .find(|p| !self.covers(*p))
     }
+}
+
+fn one_impl(y: i32, input: &Input) -> usize {
+    let field = SensorField::new(input);
+    let merged = field.row(y as i64);
+    let covered = merged
+        .iter()
+        .map(|(lo, hi)| (hi - lo) as usize + 1)
+        .sum::<usize>();
+    let beacons = field
+        .beacons
+        .iter()
+        .filter(|b| b.1 == y as i64 && merged.iter().any(|(lo, hi)| (*lo..=*hi).contains(&b.0)))
+        .count();
+    covered - beacons
+}
 
-    unreachable!()
+fn two_impl(max_x: i32, max_y: i32, input: &Input) -> Option<u64> {
+    SensorField::new(input)
+        .find_beacon((0, 0), (max_x as i64, max_y as i64))
+        .map(|(x, y)| x as u64 * 4000000 + y as u64)
 }
 
 fn parse(reader: Vec<String>) -> Input {
@@ -109,13 +213,16 @@ pub fn fifteen() -> Result<(), std::io::Error> {
     let reader = BufReader::new(file);
     let input = parse(reader.lines().map(|t| t.unwrap()).collect());
     println!("Day 15 part 1: {}", one_impl(2000000, &input));
-    println!("Day 15 part 2: {}", two_impl(4000000, 4000000, &input));
+    let beacon = two_impl(4000000, 4000000, &input).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "no uncovered position")
+    })?;
+    println!("Day 15 part 2: {}", beacon);
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_15::{one_impl, parse, two_impl};
+    use crate::day_15::{one_impl, parse, two_impl, SensorField};
 
     #[test]
     fn it_works() {
@@ -140,6 +247,131 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3
         .collect::<Vec<_>>();
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
         assert_eq!(26, one_impl(10, &parsed));
-        assert_eq!(56000011, two_impl(20, 20, &parsed));
+        assert_eq!(Some(56000011), two_impl(20, 20, &parsed));
+    }
+
+    #[test]
+    fn searches() {
+        let input = "
+Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+Sensor at x=9, y=16: closest beacon is at x=10, y=16
+Sensor at x=13, y=2: closest beacon is at x=15, y=3
+"
+        .trim()
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let parsed = parse(input);
+        let field = SensorField::new(&parsed);
+        assert_eq!(vec![(-3, 10)], field.row(16));
+        assert_eq!(vec![(-4, 9)], field.row(17));
+        assert_eq!(vec![(10, 16)], field.row(2));
+        assert_eq!(Vec::<(i64, i64)>::new(), field.row(100));
+        assert_eq!(13, one_impl(16, &parsed));
+
+        let parsed = parse(vec![
+            "Sensor at x=0, y=0: closest beacon is at x=1, y=0".to_string()
+        ]);
+        let field = SensorField::new(&parsed);
+        assert_eq!(None, field.find_beacon((-1, 0), (1, 0)));
+        assert_eq!(Some((2, 0)), field.find_beacon((-1, 0), (2, 0)));
+        assert_eq!(Some((-1, -1)), field.find_beacon((-1, -1), (1, 1)));
+        assert_eq!(
+            Some((i64::MIN, i64::MIN)),
+            field.find_beacon((i64::MIN, i64::MIN), (i64::MAX, i64::MAX))
+        );
+    }
+
+    #[test]
+    fn queries() {
+        let parsed = parse(vec![
+            "Sensor at x=0, y=0: closest beacon is at x=2, y=0".to_string(),
+            "Sensor at x=3, y=0: closest beacon is at x=4, y=0".to_string(),
+        ]);
+        let field = SensorField::new(&parsed);
+        assert!(field.covers((2, 0)));
+        assert!(field.covers((0, -2)));
+        assert!(!field.covers((3, 2)));
+        assert_eq!(vec![(0, 0), (3, 0)], field.covering((2, 0)));
+        assert_eq!(vec![(3, 0)], field.covering((4, 0)));
+        assert_eq!(
+            Vec::<(i64, i64)>::new(),
+            field.covering((i64::MAX, i64::MIN))
+        );
+
+        assert_eq!(
+            vec![(-2, -1), (2, -1), (-2, 1), (2, 1)],
+            field
+                .uncovered((-2, -1), (3, 1))
+                .take(4)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(i64::MAX - 1, i64::MIN), (i64::MAX, i64::MIN)],
+            field
+                .uncovered((i64::MAX - 1, i64::MIN), (i64::MAX, i64::MAX))
+                .take(2)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, field.uncovered((0, 0), (4, 0)).count());
+
+        assert_eq!(
+            17,
+            field.covered_area((i64::MIN, i64::MIN), (i64::MAX, i64::MAX))
+        );
+        assert_eq!(5, field.covered_area((0, 0), (4, 0)));
+        assert_eq!(10, field.covered_area((0, -1), (3, 1)));
+        assert_eq!(
+            0,
+            SensorField::new(&parse(vec![])).covered_area((0, 0), (9, 9))
+        );
+    }
+
+    #[test]
+    fn pockets() {
+        let sensors = [
+            ((6, 22), (5, 32)),
+            ((11, 0), (20, 0)),
+            ((16, 22), (7, 35)),
+            ((21, 10), (23, -5)),
+            ((20, 17), (15, 28)),
+            ((21, 2), (15, 16)),
+            ((18, 20), (30, 34)),
+            ((1, 21), (-2, 33)),
+            ((0, 1), (7, 3)),
+            ((20, 23), (29, 25)),
+        ];
+        let line = |((sx, sy), (bx, by)): ((i32, i32), (i32, i32))| {
+            format!(
+                "Sensor at x={}, y={}: closest beacon is at x={}, y={}",
+                sx, sy, bx, by
+            )
+        };
+        let parsed = parse(sensors.iter().map(|s| line(*s)).collect());
+        let field = SensorField::new(&parsed);
+        assert_eq!(Some((4, 7)), field.find_beacon((0, 0), (29, 29)));
+
+        let mut seed = 12345u64;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as i32
+        };
+        for _ in 0..300 {
+            let count = 1 + next(8) as usize;
+            let sensors = (0..count)
+                .map(|_| ((next(20), next(20)), (next(30) - 5, next(30) - 5)))
+                .collect::<Vec<_>>();
+            let parsed = parse(sensors.iter().map(|s| line(*s)).collect());
+            let field = SensorField::new(&parsed);
+            let brute = field.uncovered((0, 0), (19, 19)).next();
+            match field.find_beacon((0, 0), (19, 19)) {
+                Some(p) => {
+                    assert!(!field.covers(p) && (0..20).contains(&p.0) && (0..20).contains(&p.1))
+                }
+                None => assert_eq!(None, brute, "{:?}", sensors),
+            }
+        }
     }
 }
@@ -1,231 +1,242 @@
 use itertools::Itertools;
-use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
 use scanf::sscanf;
 
-type Input = (u8, HashMap<u8, (usize, Vec<u8>)>, HashMap<u8, String>);
+type Input = (HashMap<u8, (usize, Vec<u8>)>, HashMap<u8, String>);
 
-fn contains(set: usize, bit: u8) -> bool {
-    (set & (1 << bit)) != 0
-}
-
-fn set(set: usize, bit: u8) -> usize {
-    let next = set | (1 << (bit as usize));
-    assert!(set != next);
-    next
-}
-
-fn one_impl(input: &Input) -> usize {
-    let routes = &input.1;
-    let mut queue: BinaryHeap<(usize, usize, u8, usize)> = BinaryHeap::new();
-
-    let mut best_flow = 0;
-
-    let pos_route_count = input.1.iter().filter(|(_, (flow, _))| *flow > 0).count() as u8;
-    let fw_route_count = input.1.len() + 1;
-    let mut fw_routes: Vec<Vec<usize>> = (0..fw_route_count)
-        .map(|_| (0..fw_route_count).map(|_| usize::MAX).collect())
-        .collect();
-
-    for (key, (_flow, routes)) in &input.1 {
+fn distances(input: &Input) -> Vec<Vec<usize>> {
+    let count = input.0.len() + 1;
+    let mut dist = vec![vec![usize::MAX; count]; count];
+    for (key, (_flow, routes)) in &input.0 {
         let k = *key as usize;
-        fw_routes[k][k] = 0;
+        dist[k][k] = 0;
         for route in routes {
             let r = *route as usize;
-            fw_routes[k][r] = 1;
-            fw_routes[r][k] = 1;
+            dist[k][r] = 1;
+            dist[r][k] = 1;
         }
     }
-
-    for k in 0..fw_route_count {
-        for i in 0..fw_route_count {
-            for j in 0..fw_route_count {
-                if fw_routes[i][j] > fw_routes[i][k].saturating_add(fw_routes[k][j]) {
-                    fw_routes[i][j] = fw_routes[i][k].saturating_add(fw_routes[k][j])
+    for k in 0..count {
+        for i in 0..count {
+            for j in 0..count {
+                let through = dist[i][k].saturating_add(dist[k][j]);
+                if dist[i][j] > through {
+                    dist[i][j] = through;
                 }
             }
         }
     }
+    dist
+}
 
-    for target in 1..=pos_route_count {
-        let route_time = fw_routes[input.0 as usize][target as usize];
-        let new_time = 30usize.saturating_sub(route_time);
-        queue.push((new_time, 0, target, 0));
-    }
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Opening {
+    valve: String,
+    minute: usize,
+    pressure: usize,
+}
 
-    while let Some((mut time, flow, cur, visited)) = queue.pop() {
-        time -= 1;
-        let flow_increase = time * routes[&cur].0;
-        let new_flow = flow_increase + flow;
-        let new_visited = set(visited, cur);
-        best_flow = best_flow.max(new_flow);
+type Route = Vec<(u8, usize, usize)>;
 
-        for target in 1..=pos_route_count {
-            let route_time = fw_routes[cur as usize][target as usize];
-            if !contains(new_visited, target) && time > route_time {
-                let new_time = time - route_time;
-                queue.push((new_time, new_flow, target, new_visited));
-            }
+fn visit(
+    valves: &[(u8, usize)],
+    dist: &[Vec<usize>],
+    (cur, time, mask, pressure): (u8, usize, usize, usize),
+    route: &mut Route,
+    best: &mut Vec<(usize, Route)>,
+) {
+    if pressure > best[mask].0 {
+        best[mask] = (pressure, route.clone());
+    }
+    for (bit, &(valve, flow)) in valves.iter().enumerate() {
+        let cost = dist[cur as usize][valve as usize].saturating_add(1);
+        if mask & (1 << bit) == 0 && time > cost {
+            let left = time - cost;
+            route.push((valve, left, left * flow));
+            let state = (valve, left, mask | (1 << bit), pressure + left * flow);
+            visit(valves, dist, state, route, best);
+            route.pop();
         }
     }
-
-    best_flow
 }
 
-fn two_impl(input: &Input) -> usize {
-    type Unit = (u8, usize);
-    let routes = &input.1;
-    let mut queue: BTreeSet<(usize, usize, Unit, Option<Unit>, usize)> = BTreeSet::new();
-
-    let mut best_flow = 0;
-
-    let pos_route_count = input.1.iter().filter(|(_, (flow, _))| *flow > 0).count() as u8;
-    let fw_route_count = input.1.len() + 1;
-    let mut fw_routes: Vec<Vec<usize>> = (0..fw_route_count)
-        .map(|_| (0..fw_route_count).map(|_| usize::MAX).collect())
-        .collect();
-
-    for (key, (_flow, routes)) in &input.1 {
-        let k = *key as usize;
-        fw_routes[k][k] = 0;
-        for route in routes {
-            let r = *route as usize;
-            fw_routes[k][r] = 1;
-            fw_routes[r][k] = 1;
+fn best_per_subset(input: &Input, start: u8, time: usize) -> Vec<(usize, Route)> {
+    let valves = input
+        .0
+        .iter()
+        .filter(|(_, (flow, _))| *flow > 0)
+        .map(|(valve, (flow, _))| (*valve, *flow))
+        .sorted()
+        .collect::<Vec<_>>();
+    let dist = distances(input);
+    let mut best = vec![(0, Vec::new()); 1 << valves.len()];
+    visit(
+        &valves,
+        &dist,
+        (start, time, 0, 0),
+        &mut Vec::new(),
+        &mut best,
+    );
+    for bit in 0..valves.len() {
+        for mask in 0..best.len() {
+            if mask & (1 << bit) != 0 && best[mask ^ (1 << bit)].0 > best[mask].0 {
+                best[mask] = best[mask ^ (1 << bit)].clone();
+            }
         }
     }
+    best
+}
 
-    for k in 0..fw_route_count {
-        for i in 0..fw_route_count {
-            for j in 0..fw_route_count {
-                if fw_routes[i][j] > fw_routes[i][k].saturating_add(fw_routes[k][j]) {
-                    fw_routes[i][j] = fw_routes[i][k].saturating_add(fw_routes[k][j])
+fn best_plan(
+    input: &Input,
+    start: &str,
+    time: usize,
+    agents: usize,
+) -> Option<(usize, Vec<Vec<Opening>>)> {
+    let start = *input.1.iter().find(|(_, name)| *name == start)?.0;
+    if agents == 0 {
+        return Some((0, Vec::new()));
+    }
+    let best = best_per_subset(input, start, time);
+    let mut teams = vec![(0..best.len())
+        .map(|mask| (best[mask].0, mask))
+        .collect::<Vec<_>>()];
+    for _ in 1..agents {
+        let team = teams.last().unwrap();
+        let next = (0..best.len())
+            .map(|mask| {
+                let mut most = (team[mask].0, 0);
+                let mut sub = mask;
+                while sub > 0 {
+                    most = most.max((best[sub].0 + team[mask ^ sub].0, sub));
+                    sub = (sub - 1) & mask;
                 }
-            }
-        }
+                most
+            })
+            .collect();
+        teams.push(next);
     }
-
-    for elephant in 1..=pos_route_count {
-        for target in elephant..=pos_route_count {
-            if target != elephant {
-                let route_time = fw_routes[input.0 as usize][target as usize];
-                let elephant_time = fw_routes[input.0 as usize][elephant as usize];
-                let visited = set(set(0, target), elephant);
-                let min_time = route_time.min(elephant_time);
-                let new_time = 26usize.saturating_sub(min_time);
-                queue.insert((
-                    new_time,
-                    0,
-                    (target, route_time - min_time),
-                    Some((elephant, elephant_time - min_time)),
-                    visited,
-                ));
-            }
-        }
+    let mut mask = best.len() - 1;
+    let total = teams.last()?[mask].0;
+    let mut plan = Vec::new();
+    for team in teams.iter().rev() {
+        let sub = team[mask].1;
+        plan.push(
+            best[sub]
+                .1
+                .iter()
+                .map(|(valve, left, pressure)| Opening {
+                    valve: input.1[valve].clone(),
+                    minute: time - left,
+                    pressure: *pressure,
+                })
+                .collect(),
+        );
+        mask ^= sub;
     }
+    Some((total, plan))
+}
 
-    while let Some((mut time, mut flow, (u1_pos, mut u1_time), mut unit2, visited)) =
-        queue.pop_first()
-    {
-        time -= 1;
-        if time == 0 {
-            continue;
-        }
-        if u1_time == 0 {
-            flow += time * routes[&u1_pos].0;
-        }
-        if let Some((u2_pos, 0)) = unit2 {
-            flow += time * routes[&u2_pos].0;
-        }
-        best_flow = best_flow.max(flow);
+fn max_pressure(input: &Input, start: &str, time: usize, agents: usize) -> Option<usize> {
+    best_plan(input, start, time, agents).map(|(total, _)| total)
+}
 
-        if let (0, Some((u2_pos, 0))) = (u1_time, unit2) {
-            for u1_target in 1..=pos_route_count {
-                for u2_target in 1..=pos_route_count {
-                    if u1_target != u2_target {
-                        let u1_time = fw_routes[u1_pos as usize][u1_target as usize];
-                        let u2_time = fw_routes[u2_pos as usize][u2_target as usize];
-                        if !contains(visited, u1_target)
-                            && time > u1_time
-                            && !contains(visited, u2_target)
-                            && time > u2_time
-                        {
-                            let new_visited = set(set(visited, u1_target), u2_target);
-                            let min_time = u1_time.min(u2_time);
-                            queue.insert((
-                                time - min_time,
-                                flow,
-                                (u1_target, u1_time - min_time),
-                                Some((u2_target, u2_time - min_time)),
-                                new_visited,
-                            ));
-                        }
-                        if !contains(visited, u1_target) && time > u1_time {
-                            queue.insert((
-                                time - u1_time,
-                                flow,
-                                (u1_target, 0),
-                                None,
-                                set(visited, u1_target),
-                            ));
-                        }
-                        if !contains(visited, u2_target) && time > u2_time {
-                            queue.insert((
-                                time - u2_time,
-                                flow,
-                                (u2_target, 0),
-                                None,
-                                set(visited, u2_target),
-                            ));
-                        }
-                    }
-                }
-            }
-        } else if u1_time == 0 {
-            unit2 = unit2.map(|(p, t)| (p, t - 1));
-            for u1_target in 1..=pos_route_count {
-                let u1_time = fw_routes[u1_pos as usize][u1_target as usize];
-                if !contains(visited, u1_target) && time > u1_time {
-                    let new_visited = set(visited, u1_target);
-                    let min_time = u1_time.min(unit2.map(|(_, t)| t).unwrap_or(usize::MAX));
-                    queue.insert((
-                        time - min_time,
-                        flow,
-                        (u1_target, u1_time - min_time),
-                        unit2.map(|(p, t)| (p, t - min_time)),
-                        new_visited,
-                    ));
-                }
-            }
-            if let Some((u2_pos, u2_time)) = unit2 {
-                queue.insert((time - u2_time, flow, (u2_pos, 0), None, visited));
-            }
-        } else if let Some((u2_pos, 0)) = unit2 {
-            u1_time -= 1;
-            for u2_target in 1..=pos_route_count {
-                let u2_time = fw_routes[u2_pos as usize][u2_target as usize];
-                if !contains(visited, u2_target) && time > u2_time {
-                    let new_visited = set(visited, u2_target);
-                    let min_time = u2_time.min(u1_time);
-                    queue.insert((
-                        time - min_time,
-                        flow,
-                        (u1_pos, u1_time - min_time),
-                        Some((u2_target, u2_time - min_time)),
-                        new_visited,
-                    ));
-                }
+fn walk(input: &Input, dist: &[Vec<usize>], from: u8, to: u8) -> Vec<u8> {
+    let mut path = Vec::new();
+    let mut cur = from;
+    while cur != to {
+        cur = *input.0[&cur]
+            .1
+            .iter()
+            .find(|n| dist[**n as usize][to as usize] + 1 == dist[cur as usize][to as usize])
+            .unwrap();
+        path.push(cur);
+    }
+    path
+}
+
+#[allow(dead_code)]
+fn narrative(input: &Input, start: &str, time: usize, plan: &[Vec<Opening>]) -> Option<String> {
+    let index = |name: &str| input.1.iter().find(|(_, n)| *n == name).map(|(i, _)| *i);
+    let dist = distances(input);
+    let mut actions: Vec<Vec<String>> = vec![Vec::new(); time + 1];
+    for (agent, openings) in plan.iter().enumerate() {
+        let (who, move_, open) = match agent {
+            0 => ("You".to_string(), "move", "open"),
+            1 => ("The elephant".to_string(), "moves", "opens"),
+            n => (format!("Agent {}", n + 1), "moves", "opens"),
+        };
+        let mut cur = index(start)?;
+        for opening in openings {
+            let valve = index(&opening.valve)?;
+            let path = walk(input, &dist, cur, valve);
+            for (i, step) in path.iter().enumerate() {
+                let minute = opening.minute - path.len() + i;
+                let line = format!("{} {} to valve {}.", who, move_, input.1[step]);
+                actions[minute].push(line);
             }
-            queue.insert((time - u1_time, flow, (u1_pos, 0), None, visited));
-        } else {
-            unreachable!()
+            let line = format!("{} {} valve {}.", who, open, opening.valve);
+            actions[opening.minute].push(line);
+            cur = valve;
         }
     }
+    let opened = plan
+        .iter()
+        .flatten()
+        .map(|o| {
+            (
+                o.minute,
+                o.valve.clone(),
+                input.0[&index(&o.valve).unwrap()].0,
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut out = Vec::new();
+    for (minute, actions) in actions.iter().enumerate().skip(1) {
+        let mut lines = vec![format!("== Minute {} ==", minute)];
+        let open = opened
+            .iter()
+            .filter(|(m, _, _)| *m < minute)
+            .sorted_by(|a, b| a.1.cmp(&b.1))
+            .collect::<Vec<_>>();
+        let released = open.iter().map(|(_, _, flow)| flow).sum::<usize>();
+        let names = open
+            .iter()
+            .map(|(_, name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        lines.push(match names.len() {
+            0 => "No valves are open.".to_string(),
+            1 => format!(
+                "Valve {} is open, releasing {} pressure.",
+                names[0], released
+            ),
+            2 => format!(
+                "Valves {} and {} are open, releasing {} pressure.",
+                names[0], names[1], released
+            ),
+            n => format!(
+                "Valves {}, and {} are open, releasing {} pressure.",
+                names[..n - 1].join(", "),
+                names[n - 1],
+                released
+            ),
+        });
+        lines.extend(actions.iter().cloned());
+        out.push(lines.join("\n"));
+    }
+    Some(out.join("\n\n") + "\n")
+}
+
+fn one_impl(input: &Input) -> Option<usize> {
+    max_pressure(input, "AA", 30, 1)
+}
 
-    best_flow
+fn two_impl(input: &Input) -> Option<usize> {
+    max_pressure(input, "AA", 26, 2)
 }
 
 fn parse(reader: Vec<String>) -> Input {
@@ -291,7 +302,6 @@ fn parse(reader: Vec<String>) -> Input {
     }
 
     (
-        translation["AA"],
         valves,
         translation.into_iter().map(|(a, b)| (b, a)).collect(),
     )
@@ -301,14 +311,15 @@ pub fn sixteen() -> Result<(), std::io::Error> {
     let file = File::open("input/16_input").unwrap();
     let reader = BufReader::new(file);
     let input = parse(reader.lines().map(|t| t.unwrap()).collect());
-    println!("Day 16 part 1: {}", one_impl(&input));
-    println!("Day 16 part 2: {}", two_impl(&input));
+    let no_start = || std::io::Error::new(std::io::ErrorKind::InvalidData, "no valve AA");
+    println!("Day 16 part 1: {}", one_impl(&input).ok_or_else(no_start)?);
+    println!("Day 16 part 2: {}", two_impl(&input).ok_or_else(no_start)?);
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_16::{one_impl, parse, two_impl};
+    use crate::day_16::{best_plan, max_pressure, narrative, one_impl, parse, two_impl, Opening};
 
     #[test]
     fn it_works() {
@@ -328,7 +339,127 @@ Valve JJ has flow rate=21; tunnel leads to valve II
         .lines()
         .collect::<Vec<_>>();
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
-        assert_eq!(1651, one_impl(&parsed));
-        assert_eq!(1707, two_impl(&parsed));
+        assert_eq!(Some(1651), one_impl(&parsed));
+        assert_eq!(Some(1707), two_impl(&parsed));
+    }
+
+    #[test]
+    fn agents() {
+        let input = "
+Valve AA has flow rate=0; tunnels lead to valves BB, CC, DD
+Valve BB has flow rate=10; tunnel leads to valve AA
+Valve CC has flow rate=20; tunnel leads to valve AA
+Valve DD has flow rate=30; tunnel leads to valve AA
+"
+        .trim()
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let parsed = parse(input);
+        assert_eq!(Some(0), max_pressure(&parsed, "AA", 2, 1));
+        assert_eq!(Some(30), max_pressure(&parsed, "AA", 3, 1));
+        assert_eq!(Some(30 * 3), max_pressure(&parsed, "AA", 5, 1));
+        assert_eq!(Some(30 + 20), max_pressure(&parsed, "AA", 3, 2));
+        assert_eq!(Some(30 + 20 + 10), max_pressure(&parsed, "AA", 3, 3));
+        assert_eq!(Some(30 + 20 + 10), max_pressure(&parsed, "AA", 3, 5));
+        assert_eq!(Some(0), max_pressure(&parsed, "BB", 1, 1));
+        assert_eq!(Some(10 * 2), max_pressure(&parsed, "BB", 3, 1));
+        assert_eq!(None, max_pressure(&parsed, "ZZ", 30, 1));
+    }
+
+    #[test]
+    fn plans() {
+        let input = "
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II
+"
+        .trim()
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let parsed = parse(input);
+        let opening = |valve: &str, minute, pressure| Opening {
+            valve: valve.to_string(),
+            minute,
+            pressure,
+        };
+
+        let (total, plan) = best_plan(&parsed, "AA", 30, 1).unwrap();
+        assert_eq!(1651, total);
+        assert_eq!(
+            vec![vec![
+                opening("DD", 2, 560),
+                opening("BB", 5, 325),
+                opening("JJ", 9, 441),
+                opening("HH", 17, 286),
+                opening("EE", 21, 27),
+                opening("CC", 24, 12),
+            ]],
+            plan
+        );
+        let story = narrative(&parsed, "AA", 30, &plan).unwrap();
+        let expected = "
+== Minute 1 ==
+No valves are open.
+You move to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve CC.
+
+== Minute 4 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve BB.
+
+== Minute 5 ==
+Valve DD is open, releasing 20 pressure.
+You open valve BB.
+
+== Minute 6 ==
+Valves BB and DD are open, releasing 33 pressure.
+You move to valve AA.
+";
+        assert!(story.starts_with(&expected[1..]));
+        assert!(story.contains(
+            "
+== Minute 10 ==
+Valves BB, DD, and JJ are open, releasing 54 pressure.
+You move to valve II.
+"
+        ));
+        assert!(story.ends_with(
+            "
+== Minute 30 ==
+Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure.
+"
+        ));
+
+        let (total, plan) = best_plan(&parsed, "AA", 26, 2).unwrap();
+        assert_eq!(1707, total);
+        assert_eq!(2, plan.len());
+        let pressure = plan.iter().flatten().map(|o| o.pressure).sum::<usize>();
+        assert_eq!(1707, pressure);
+        let story = narrative(&parsed, "AA", 26, &plan).unwrap();
+        assert!(story.contains("You open valve "));
+        assert!(story.contains("The elephant opens valve "));
+        assert!(
+            story.ends_with("Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure.\n")
+        );
+
+        assert_eq!(None, best_plan(&parsed, "ZZ", 30, 1));
+        assert_eq!(Some((0, vec![vec![]])), best_plan(&parsed, "AA", 1, 1));
+        assert_eq!(Some((0, vec![])), best_plan(&parsed, "AA", 30, 0));
     }
 }
@@ -11,7 +11,7 @@ type Blueprint = (usize, usize, usize, (usize, usize), (usize, usize));
 type Input = Vec<Blueprint>;
 
 fn div_ceil(lhs: usize, rhs: usize) -> usize {
-    if lhs % rhs == 0 {
+    if lhs.is_multiple_of(rhs) {
         lhs / rhs
     } else {
         (lhs / rhs) + 1
@@ -114,11 +114,9 @@ fn simulate(bp: Blueprint, max_time: usize) -> usize {
                 );
             }
         } else {
-            let mut rpm = item.1 .3;
             let mut geodes = item.2 .3;
-            for _ in item.0..max_time {
+            for (_, rpm) in (item.0..max_time).zip(item.1 .3..) {
                 geodes += rpm;
-                rpm += 1;
             }
             queue.insert((max_time, (0, 0, 0, 0), (0, 0, 0, geodes)));
             continue;
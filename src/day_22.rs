@@ -185,7 +185,7 @@ fn get_translations(grid: &Grid<char>) -> HashMap<(usize, usize), (usize, usize)
             out.insert(cur, other);
             out.insert(other, cur);
         } else if cur_c.is_ascii_uppercase() {
-            stacks.entry(cur_c).or_insert_with(Vec::new).push(cur);
+            stacks.entry(cur_c).or_default().push(cur);
         }
 
         let next = [(1, 0), (-1, 0), (0, 1), (0, -1)]
@@ -29,9 +29,8 @@ fn one_impl(input: &Input) -> (i32, usize) {
             {
                 let elves = &elves;
                 if !(-1..=1)
-                    .into_iter()
                     .flat_map(|dy| {
-                        (-1..=1).into_iter().filter_map(move |dx| {
+                        (-1..=1).filter_map(move |dx| {
                             if dx != 0 || dy != 0 {
                                 Some((x + dx, y + dy))
                             } else {
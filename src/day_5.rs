@@ -1,51 +1,175 @@
+use std::fmt;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
-type Input = (Vec<Vec<char>>, Vec<(usize, usize, usize)>);
+type Stacks = Vec<Vec<char>>;
+type Input = (Stacks, Vec<(usize, usize, usize)>);
 
-fn one_impl(input: &Input) -> String {
-    let (mut crates, operations) = input.clone();
-    for (count, from, to) in operations {
-        for _ in 0..count {
-            let p = crates[from].pop().unwrap();
-            crates[to].push(p);
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Crane {
+    CrateMover9000,
+    CrateMover9001,
+    Capacity(usize),
+}
+
+impl Crane {
+    fn capacity(&self) -> usize {
+        match self {
+            Crane::CrateMover9000 => 1,
+            Crane::CrateMover9001 => usize::MAX,
+            Crane::Capacity(n) => *n,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum CraneError {
+    NoCapacity,
+    NoSuchStack {
+        instruction: usize,
+        stack: usize,
+    },
+    NotEnoughCrates {
+        instruction: usize,
+        stack: usize,
+        requested: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for CraneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CraneError::NoCapacity => write!(f, "crane cannot lift any crates"),
+            CraneError::NoSuchStack { instruction, stack } => {
+                write!(f, "instruction {}: no stack {}", instruction, stack)
+            }
+            CraneError::NotEnoughCrates {
+                instruction,
+                stack,
+                requested,
+                available,
+            } => write!(
+                f,
+                "instruction {}: cannot move {} crates from stack {} holding {}",
+                instruction, requested, stack, available
+            ),
         }
     }
-    crates.iter().map(|c| c.last().unwrap()).collect()
 }
 
-fn two_impl(input: &Input) -> String {
+impl std::error::Error for CraneError {}
+
+fn operate(
+    input: &Input,
+    crane: Crane,
+    mut trace: Option<&mut Vec<Stacks>>,
+) -> Result<Stacks, CraneError> {
+    let capacity = crane.capacity();
+    if capacity == 0 {
+        return Err(CraneError::NoCapacity);
+    }
     let (mut crates, operations) = input.clone();
-    for (count, from, to) in operations {
-        let mut popped = Vec::new();
-        for _ in 0..count {
-            popped.push(crates[from].pop().unwrap());
+    for (i, &(count, from, to)) in operations.iter().enumerate() {
+        let instruction = i + 1;
+        let index = |stack: usize| {
+            stack
+                .checked_sub(1)
+                .filter(|s| *s < crates.len())
+                .ok_or(CraneError::NoSuchStack { instruction, stack })
+        };
+        let (from, to) = (index(from)?, index(to)?);
+        let available = crates[from].len();
+        if available < count {
+            return Err(CraneError::NotEnoughCrates {
+                instruction,
+                stack: from + 1,
+                requested: count,
+                available,
+            });
+        }
+        let mut remaining = count;
+        while remaining > 0 {
+            let lift = remaining.min(capacity);
+            let at = crates[from].len() - lift;
+            let lifted = crates[from].split_off(at);
+            crates[to].extend(lifted);
+            remaining -= lift;
+        }
+        if let Some(trace) = trace.as_mut() {
+            trace.push(crates.clone());
         }
-        popped.reverse();
-        crates[to].extend(&mut popped.into_iter());
     }
-    crates.iter().map(|c| c.last().unwrap()).collect()
+    Ok(crates)
 }
 
-fn parse(reader: Vec<String>) -> Input {
-    let mut crates = Vec::new();
-    let mut operations = Vec::new();
-    let mut iter = reader.iter();
-    for s in iter.by_ref() {
-        if s.is_empty() {
-            break;
+fn tops(crates: &[Vec<char>]) -> String {
+    crates.iter().filter_map(|c| c.last()).collect()
+}
+
+fn one_impl(input: &Input) -> Result<String, CraneError> {
+    Ok(tops(&operate(input, Crane::CrateMover9000, None)?))
+}
+
+fn two_impl(input: &Input) -> Result<String, CraneError> {
+    Ok(tops(&operate(input, Crane::CrateMover9001, None)?))
+}
+
+fn render(crates: &[Vec<char>]) -> String {
+    let height = crates.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for y in (0..height).rev() {
+        let row = crates
+            .iter()
+            .map(|c| match c.get(y) {
+                Some(ch) => format!("[{}]", ch),
+                None => "   ".to_string(),
+            })
+            .collect::<Vec<_>>();
+        lines.push(row.join(" "));
+    }
+    let footer = (1..=crates.len())
+        .map(|i| format!("{:^3}", i))
+        .collect::<Vec<_>>();
+    lines.push(footer.join(" "));
+    lines.join("\n")
+}
+
+#[allow(dead_code)]
+fn render_moves(input: &Input, crane: Crane) -> Result<Vec<String>, CraneError> {
+    let mut trace = Vec::new();
+    operate(input, crane, Some(&mut trace))?;
+    Ok(trace.iter().map(|crates| render(crates)).collect())
+}
+
+fn parse_drawing(drawing: &[String]) -> Vec<Vec<char>> {
+    let (footer, rows) = drawing.split_last().unwrap();
+    let count = footer.split_whitespace().count();
+    let mut crates = vec![Vec::new(); count];
+    for row in rows.iter().rev() {
+        let row = row.chars().collect::<Vec<_>>();
+        for (i, stack) in crates.iter_mut().enumerate() {
+            match row.get(i * 4 + 1) {
+                Some(' ') | None => {}
+                Some(c) => stack.push(*c),
+            }
         }
-        let mut vec = s.chars().collect::<Vec<_>>();
-        vec.reverse();
-        crates.push(vec);
     }
-    for s in iter {
+    crates
+}
+
+fn parse(reader: Vec<String>) -> Input {
+    let mut operations = Vec::new();
+    let split = reader.iter().position(|s| s.is_empty()).unwrap();
+    let crates = parse_drawing(&reader[..split]);
+    for s in &reader[split + 1..] {
         let parts = s.split(' ').collect::<Vec<_>>();
         operations.push((
             parts[1].parse().unwrap(),
-            parts[3].parse::<usize>().unwrap() - 1,
-            parts[5].parse::<usize>().unwrap() - 1,
+            parts[3].parse().unwrap(),
+            parts[5].parse().unwrap(),
         ));
     }
     (crates, operations)
@@ -55,21 +179,25 @@ pub fn five() -> Result<(), std::io::Error> {
     let file = File::open("input/5_input")?;
     let reader = BufReader::new(file);
     let input = parse(reader.lines().map(|t| t.unwrap()).collect());
-    println!("Day 5 part 1: {}", one_impl(&input));
-    println!("Day 5 part 2: {}", two_impl(&input));
+    let to_io = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+    println!("Day 5 part 1: {}", one_impl(&input).map_err(to_io)?);
+    println!("Day 5 part 2: {}", two_impl(&input).map_err(to_io)?);
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_5::{one_impl, parse, two_impl};
+    use crate::day_5::{
+        one_impl, operate, parse, render, render_moves, two_impl, Crane, CraneError,
+    };
 
     #[test]
     fn it_works() {
         let input = [
-            "NZ",
-            "DCM",
-            "P",
+            "    [D]",
+            "[N] [C]    ",
+            "[Z] [M] [P]",
+            " 1   2   3 ",
             "",
             "move 1 from 2 to 1",
             "move 3 from 1 to 3",
@@ -77,7 +205,99 @@ mod tests {
             "move 1 from 1 to 2",
         ];
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
-        assert_eq!("CMZ", one_impl(&parsed));
-        assert_eq!("MCD", two_impl(&parsed));
+        assert_eq!(Ok("CMZ".to_string()), one_impl(&parsed));
+        assert_eq!(Ok("MCD".to_string()), two_impl(&parsed));
+    }
+
+    #[test]
+    fn renders() {
+        let input = [
+            "    [D]    ",
+            "[N] [C]    ",
+            "[Z] [M] [P]",
+            " 1   2   3 ",
+            "",
+            "move 1 from 2 to 1",
+            "move 3 from 1 to 3",
+        ];
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        assert_eq!(input[..4].join("\n"), render(&parsed.0));
+        let frames = render_moves(&parsed, Crane::CrateMover9000).unwrap();
+        assert_eq!(
+            ["[D]        ", "[N] [C]    ", "[Z] [M] [P]", " 1   2   3 "].join("\n"),
+            frames[0]
+        );
+        assert_eq!(
+            [
+                "        [Z]",
+                "        [N]",
+                "    [C] [D]",
+                "    [M] [P]",
+                " 1   2   3 "
+            ]
+            .join("\n"),
+            frames[1]
+        );
+    }
+
+    #[test]
+    fn cranes() {
+        let crates = vec![vec!['A', 'B', 'C', 'D', 'E'], vec![]];
+        let input = (crates, vec![(5, 1, 2)]);
+        let moved = |crane| operate(&input, crane, None).map(|c| c[1].iter().collect::<String>());
+        assert_eq!(Ok("EDCBA".to_string()), moved(Crane::CrateMover9000));
+        assert_eq!(Ok("ABCDE".to_string()), moved(Crane::CrateMover9001));
+        assert_eq!(Ok("DEBCA".to_string()), moved(Crane::Capacity(2)));
+        assert_eq!(Err(CraneError::NoCapacity), moved(Crane::Capacity(0)));
+
+        let mut trace = Vec::new();
+        let input = (vec![vec!['A', 'B'], vec![]], vec![(1, 1, 2), (1, 1, 2)]);
+        operate(&input, Crane::CrateMover9000, Some(&mut trace)).unwrap();
+        assert_eq!(
+            vec![vec![vec!['A'], vec!['B']], vec![vec![], vec!['B', 'A']]],
+            trace
+        );
+    }
+
+    #[test]
+    fn invalid_moves() {
+        let crates = vec![vec!['A'], vec!['B']];
+        let input = (crates.clone(), vec![(1, 1, 2), (3, 2, 1)]);
+        assert_eq!(
+            Err(CraneError::NotEnoughCrates {
+                instruction: 2,
+                stack: 2,
+                requested: 3,
+                available: 2
+            }),
+            operate(&input, Crane::CrateMover9001, None)
+        );
+        let input = (crates.clone(), vec![(1, 1, 3)]);
+        assert_eq!(
+            Err(CraneError::NoSuchStack {
+                instruction: 1,
+                stack: 3
+            }),
+            operate(&input, Crane::CrateMover9000, None)
+        );
+        let input = parse(
+            [
+                "[A] [B]",
+                " 1   2 ",
+                "",
+                "move 1 from 1 to 2",
+                "move 1 from 0 to 1",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        );
+        assert_eq!(
+            Err(CraneError::NoSuchStack {
+                instruction: 2,
+                stack: 0
+            }),
+            operate(&input, Crane::CrateMover9000, None)
+        );
     }
 }
@@ -1,52 +1,119 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
-type Input = Vec<char>;
+type Input = Vec<u8>;
 
-fn one_impl(input: &Input) -> usize {
-    for i in 0..input.len() - 3 {
-        let buf = input[i..i + 4].iter().collect::<HashSet<_>>();
-        if buf.len() == 4 {
-            return i + 4;
+struct Markers<I> {
+    bytes: I,
+    window: usize,
+    seen: VecDeque<u8>,
+    counts: [usize; 256],
+    distinct: usize,
+    pos: usize,
+}
+
+impl<I: Iterator<Item = u8>> Markers<I> {
+    fn new(bytes: I, window: usize) -> Markers<I> {
+        Markers {
+            bytes,
+            window,
+            seen: VecDeque::with_capacity(window + 1),
+            counts: [0; 256],
+            distinct: 0,
+            pos: 0,
         }
     }
-    unimplemented!()
 }
 
-fn two_impl(input: &Input) -> usize {
-    for i in 0..input.len() - 14 {
-        let buf = input[i..i + 14].iter().collect::<HashSet<_>>();
-        if buf.len() == 14 {
-            return i + 14;
+impl<I: Iterator<Item = u8>> Iterator for Markers<I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        for b in self.bytes.by_ref() {
+            self.pos += 1;
+            self.seen.push_back(b);
+            self.counts[b as usize] += 1;
+            if self.counts[b as usize] == 1 {
+                self.distinct += 1;
+            }
+            if self.seen.len() > self.window {
+                let old = self.seen.pop_front().unwrap();
+                self.counts[old as usize] -= 1;
+                if self.counts[old as usize] == 0 {
+                    self.distinct -= 1;
+                }
+            }
+            if self.seen.len() == self.window && self.distinct == self.window {
+                return Some(self.pos);
+            }
         }
+        None
+    }
+}
+
+fn find_marker(input: &Input, window: usize) -> Option<usize> {
+    Markers::new(input.iter().copied(), window).next()
+}
+
+#[allow(dead_code)]
+fn all_markers(input: &Input, window: usize) -> Vec<usize> {
+    Markers::new(input.iter().copied(), window).collect()
+}
+
+#[allow(dead_code)]
+fn scan_reader<R: BufRead>(reader: R, window: usize) -> Result<Vec<usize>, std::io::Error> {
+    let mut error = None;
+    let bytes = reader
+        .bytes()
+        .map_while(|b| b.map_err(|e| error = Some(e)).ok())
+        .take_while(|b| *b != b'\n');
+    let found = Markers::new(bytes, window).collect();
+    match error {
+        Some(e) => Err(e),
+        None => Ok(found),
     }
-    unimplemented!()
 }
 
 fn parse(reader: Vec<String>) -> Input {
-    reader[0].chars().collect()
+    reader[0].bytes().collect()
 }
 
 pub fn six() -> Result<(), std::io::Error> {
     let file = File::open("input/6_input")?;
     let reader = BufReader::new(file);
     let input = parse(reader.lines().map(|t| t.unwrap()).collect());
-    println!("Day 6 part 1: {}", one_impl(&input));
-    println!("Day 6 part 2: {}", two_impl(&input));
+    for (part, window) in [(1, 4), (2, 14)] {
+        let marker = find_marker(&input, window).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "no marker found")
+        })?;
+        println!("Day 6 part {}: {}", part, marker);
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_6::{one_impl, parse, two_impl};
+    use crate::day_6::{all_markers, find_marker, parse, scan_reader};
 
     #[test]
     fn it_works() {
         let input = ["mjqjpqmgbljsphdztnvjfqwrcgsmlb"];
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
-        assert_eq!(7, one_impl(&parsed));
-        assert_eq!(19, two_impl(&parsed));
+        assert_eq!(Some(7), find_marker(&parsed, 4));
+        assert_eq!(Some(19), find_marker(&parsed, 14));
+    }
+
+    #[test]
+    fn markers() {
+        let parsed = parse(vec!["abcab".to_string()]);
+        assert_eq!(vec![3, 4, 5], all_markers(&parsed, 3));
+        assert_eq!(None, find_marker(&parsed, 4));
+        assert_eq!(None, find_marker(&parsed, 14));
+        assert_eq!(None, find_marker(&parse(vec!["aaaa".to_string()]), 2));
+        let stream = "nppdvjthqldpwncqszvftbrmjlhg\n".as_bytes();
+        assert_eq!(Some(&6), scan_reader(stream, 4).unwrap().first());
+        assert_eq!(Some(&23), scan_reader(stream, 14).unwrap().first());
     }
 }
@@ -1,104 +1,500 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::iter::Peekable;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Dir {
-    files: Vec<(usize, String)>,
-    dirs: HashMap<String, Dir>,
+    files: BTreeMap<String, usize>,
+    dirs: BTreeMap<String, Dir>,
+    listed: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    Dir(String),
+    File(usize, String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Command {
+    Cd(String),
+    Ls(Vec<Entry>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FsError {
+    Syntax { line: usize, text: String },
+    NotADirectory { line: usize, path: String },
+    Contradiction { line: usize, path: String },
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsError::Syntax { line, text } => write!(f, "line {}: cannot parse {:?}", line, text),
+            FsError::NotADirectory { line, path } => {
+                write!(f, "line {}: {} is not a directory", line, path)
+            }
+            FsError::Contradiction { line, path } => {
+                write!(
+                    f,
+                    "line {}: listing contradicts earlier view of {}",
+                    line, path
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
+fn join(cwd: &[String], name: &str) -> String {
+    let mut path = String::new();
+    for component in cwd.iter().map(|s| s.as_str()).chain(Some(name)) {
+        path.push('/');
+        path.push_str(component);
+    }
+    path
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            glob_match(&pattern[1..], text) || (!text.is_empty() && glob_match(pattern, &text[1..]))
+        }
+        (Some('?'), Some(_)) => glob_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) if p == t => glob_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+#[allow(dead_code)]
 impl Dir {
     pub fn new() -> Dir {
         Dir::default()
     }
+
+    fn get(&self, path: &[String]) -> Option<&Dir> {
+        path.iter().try_fold(self, |dir, name| dir.dirs.get(name))
+    }
+
+    fn get_mut(&mut self, path: &[String]) -> Option<&mut Dir> {
+        path.iter()
+            .try_fold(self, |dir, name| dir.dirs.get_mut(name))
+    }
+
+    fn list(&mut self, entries: &[Entry]) -> Result<(), String> {
+        let mut files = BTreeMap::new();
+        let mut dirs = BTreeSet::new();
+        for entry in entries {
+            let (name, fresh) = match entry {
+                Entry::Dir(name) => (name, !files.contains_key(name) && dirs.insert(name)),
+                Entry::File(size, name) => (
+                    name,
+                    !dirs.contains(name) && files.insert(name, *size).is_none(),
+                ),
+            };
+            if !fresh {
+                return Err(name.clone());
+            }
+        }
+        for (name, size) in &self.files {
+            if files.get(name) != Some(size) {
+                return Err(name.clone());
+            }
+        }
+        for name in self.dirs.keys() {
+            if !dirs.contains(name) {
+                return Err(name.clone());
+            }
+        }
+        if self.listed {
+            let unseen = files
+                .keys()
+                .chain(dirs.iter())
+                .find(|name| !self.files.contains_key(**name) && !self.dirs.contains_key(**name));
+            if let Some(name) = unseen {
+                return Err(name.to_string());
+            }
+        }
+        for (name, size) in files {
+            self.files.insert(name.clone(), size);
+        }
+        for name in dirs {
+            self.dirs.entry(name.clone()).or_default();
+        }
+        self.listed = true;
+        Ok(())
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.files.values().sum::<usize>()
+            + self.dirs.values().map(|d| d.total_size()).sum::<usize>()
+    }
+
+    pub fn size(&self, path: &str) -> Option<usize> {
+        let mut components = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let last = match components.pop() {
+            Some(last) => last,
+            None => return Some(self.total_size()),
+        };
+        let dir = self.get(&components)?;
+        dir.files
+            .get(&last)
+            .copied()
+            .or_else(|| dir.dirs.get(&last).map(|d| d.total_size()))
+    }
+
+    fn walk(&self) -> Vec<(String, &Dir)> {
+        let mut out = vec![("/".to_string(), self)];
+        let mut stack = vec![(Vec::new(), self)];
+        while let Some((cwd, dir)) = stack.pop() {
+            for (name, child) in dir.dirs.iter().rev() {
+                out.push((join(&cwd, name), child));
+                let mut path = cwd.clone();
+                path.push(name.clone());
+                stack.push((path, child));
+            }
+        }
+        out
+    }
+
+    pub fn largest_dirs(&self, n: usize) -> Vec<(String, usize)> {
+        let (_, mut dirs) = sizes(self);
+        dirs.sort_by(|(p1, s1), (p2, s2)| s2.cmp(s1).then(p1.cmp(p2)));
+        dirs.truncate(n);
+        dirs
+    }
+
+    fn render_tree_(&self, name: &str, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!(
+            "{}- {} (dir, size={})\n",
+            indent,
+            name,
+            self.total_size()
+        ));
+        let mut entries = self
+            .dirs
+            .keys()
+            .map(|name| (name, None))
+            .chain(self.files.iter().map(|(name, size)| (name, Some(size))))
+            .collect::<Vec<_>>();
+        entries.sort();
+        for (name, size) in entries {
+            match size {
+                Some(size) => {
+                    out.push_str(&format!("{}  - {} (file, size={})\n", indent, name, size))
+                }
+                None => self.dirs[name].render_tree_(name, depth + 1, out),
+            }
+        }
+    }
+
+    pub fn render_tree(&self) -> String {
+        let mut out = String::new();
+        self.render_tree_("/", 0, &mut out);
+        out
+    }
+
+    fn du_(
+        &self,
+        name: &str,
+        depth: usize,
+        max_depth: Option<usize>,
+        width: usize,
+        out: &mut String,
+    ) {
+        out.push_str(&format!(
+            "{:>width$}  {}{}\n",
+            self.total_size(),
+            "  ".repeat(depth),
+            name,
+            width = width
+        ));
+        if max_depth.map(|max| depth >= max).unwrap_or(false) {
+            return;
+        }
+        let mut children = self
+            .dirs
+            .iter()
+            .map(|(name, dir)| (dir.total_size(), name, dir))
+            .collect::<Vec<_>>();
+        children.sort_by(|(s1, n1, _), (s2, n2, _)| s2.cmp(s1).then(n1.cmp(n2)));
+        for (_, name, dir) in children {
+            dir.du_(name, depth + 1, max_depth, width, out);
+        }
+    }
+
+    pub fn du(&self, max_depth: Option<usize>) -> String {
+        let width = self.total_size().to_string().len();
+        let mut out = String::new();
+        self.du_("/", 0, max_depth, width, &mut out);
+        out
+    }
+
+    pub fn find(&self, pattern: &str) -> Vec<(String, usize)> {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+        let mut found = Vec::new();
+        for (path, dir) in self.walk() {
+            for (name, size) in &dir.files {
+                let full = if path == "/" {
+                    format!("/{}", name)
+                } else {
+                    format!("{}/{}", path, name)
+                };
+                let subject = if pattern.first() == Some(&'/') {
+                    &full
+                } else {
+                    name
+                };
+                if glob_match(&pattern, &subject.chars().collect::<Vec<_>>()) {
+                    found.push((full, *size));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
 }
 
 type Input = Dir;
 
-fn sizes(input: &Dir) -> (usize, Vec<usize>) {
-    let mut this = input.files.iter().map(|(size, _)| size).sum::<usize>();
-    let mut inner: Vec<usize> = Vec::new();
-    for d in input.dirs.values() {
-        let (actual, dirs) = sizes(d);
-        this += actual;
-        inner.extend(&mut dirs.into_iter());
+fn sizes_(dir: &Dir, cwd: &mut Vec<String>, out: &mut Vec<(String, usize)>) -> usize {
+    let mut this = dir.files.values().sum::<usize>();
+    for (name, d) in &dir.dirs {
+        cwd.push(name.clone());
+        this += sizes_(d, cwd, out);
+        cwd.pop();
+    }
+    let path = match cwd.split_last() {
+        Some((name, parent)) => join(parent, name),
+        None => "/".to_string(),
+    };
+    out.push((path, this));
+    this
+}
+
+fn sizes(input: &Dir) -> (usize, Vec<(String, usize)>) {
+    let mut dirs = Vec::new();
+    let total = sizes_(input, &mut Vec::new(), &mut dirs);
+    (total, dirs)
+}
+
+fn space_needed(disk: usize, required: usize, used: usize) -> usize {
+    required.saturating_sub(disk.saturating_sub(used))
+}
+
+fn smallest_deletion(input: &Input, disk: usize, required: usize) -> Option<(String, usize)> {
+    let (used, dirs) = sizes(input);
+    let needed = space_needed(disk, required, used);
+    dirs.into_iter()
+        .filter(|(_, size)| *size >= needed)
+        .min_by(|(p1, s1), (p2, s2)| s1.cmp(s2).then(p1.cmp(p2)))
+}
+
+fn or_shifted(dst: &mut [u64], src: &[u64], shift: usize) {
+    let (words, bits) = (shift / 64, shift % 64);
+    for (i, word) in src.iter().enumerate().filter(|(_, w)| **w != 0) {
+        if let Some(d) = dst.get_mut(i + words) {
+            *d |= word << bits;
+        }
+        if bits > 0 {
+            if let Some(d) = dst.get_mut(i + words + 1) {
+                *d |= word >> (64 - bits);
+            }
+        }
+    }
+}
+
+fn has_bit(set: &[u64], bit: usize) -> bool {
+    set[bit / 64] & (1 << (bit % 64)) != 0
+}
+
+// Only the set on entry to each open directory is kept, so memory is
+// bounded by depth rather than by the number of directories.
+fn reachable_(dir: &Dir, reach: &mut [u64]) -> usize {
+    let before = reach.to_vec();
+    let mut size = dir.files.values().sum::<usize>();
+    for d in dir.dirs.values() {
+        size += reachable_(d, reach);
+    }
+    or_shifted(reach, &before, size);
+    size
+}
+
+fn reachable(forest: &[&Dir], cap: usize) -> Vec<u64> {
+    let mut reach = vec![0u64; cap / 64 + 1];
+    reach[0] = 1;
+    for dir in forest {
+        reachable_(dir, &mut reach);
+    }
+    reach
+}
+
+fn realize_(dir: &Dir, cwd: &mut Vec<String>, target: usize, out: &mut Vec<String>) {
+    if target == 0 {
+        return;
+    }
+    if target == dir.total_size() {
+        out.push(match cwd.split_last() {
+            Some((name, parent)) => join(parent, name),
+            None => "/".to_string(),
+        });
+        return;
+    }
+    let children = dir.dirs.iter().collect::<Vec<_>>();
+    let mut remaining = target;
+    for k in (0..children.len()).rev() {
+        let (name, d) = children[k];
+        let before = children[..k].iter().map(|(_, d)| *d).collect::<Vec<_>>();
+        let before = reachable(&before, remaining);
+        let child = reachable(&[d], remaining);
+        let part = (0..=remaining)
+            .find(|b| has_bit(&child, *b) && has_bit(&before, remaining - b))
+            .unwrap();
+        cwd.push(name.clone());
+        realize_(d, cwd, part, out);
+        cwd.pop();
+        remaining -= part;
+    }
+}
+
+// Smallest means the fewest bytes freed, not the fewest directories.
+#[allow(dead_code)]
+fn smallest_deletion_set(
+    input: &Input,
+    disk: usize,
+    required: usize,
+) -> Option<(usize, Vec<String>)> {
+    let (used, dirs) = sizes(input);
+    let needed = space_needed(disk, required, used);
+    if needed == 0 {
+        return Some((0, Vec::new()));
     }
-    inner.push(this);
-    (this, inner)
+    let cap = dirs
+        .iter()
+        .map(|(_, size)| *size)
+        .filter(|size| *size >= needed)
+        .min()?;
+    let reach = reachable(&[input], cap);
+    let freed = (needed..=cap).find(|t| has_bit(&reach, *t))?;
+    let mut paths = Vec::new();
+    realize_(input, &mut Vec::new(), freed, &mut paths);
+    paths.sort();
+    Some((freed, paths))
 }
 
 fn one_impl(input: &Input) -> usize {
     let (_, dirs) = sizes(input);
-    dirs.iter().copied().filter(|s| *s <= 100000).sum()
-}
-
-fn two_impl(input: &Input) -> usize {
-    let total = 70000000;
-    let required = 30000000;
-    let (actual, dirs) = sizes(input);
-    dirs.iter()
-        .copied()
-        .filter(|s| total - actual + s > required)
-        .min()
-        .unwrap()
-}
-
-fn parse_<I>(fs: &mut Dir, iter: &mut Peekable<I>) -> Option<()>
-where
-    I: Iterator<Item = String>,
-{
-    while let Some(s) = iter.next() {
-        if s.contains("cd") {
-            let dir = s.split(' ').collect::<Vec<_>>()[2];
-            if dir == "/" {
-                return None;
-            } else if dir == ".." {
-                return Some(());
-            } else {
-                if !fs.dirs.contains_key(dir) {
-                    fs.dirs.insert(dir.to_string(), Dir::new());
-                }
-                parse_(fs.dirs.get_mut(dir).unwrap(), iter)?;
-            }
-        } else if s.contains("ls") {
-            while iter.peek().filter(|s| !s.contains('$')).is_some() {
-                let next = iter.next().unwrap();
-                let row = next.split(' ').collect::<Vec<_>>();
-                if !row[0].starts_with("dir") {
-                    fs.files.push((row[0].parse().unwrap(), row[1].to_string()));
+    dirs.iter().map(|(_, s)| *s).filter(|s| *s <= 100000).sum()
+}
+
+fn two_impl(disk: usize, required: usize, input: &Input) -> usize {
+    smallest_deletion(input, disk, required).unwrap().1
+}
+
+fn parse_session(reader: Vec<String>) -> Result<Vec<(usize, Command)>, FsError> {
+    let mut session: Vec<(usize, Command)> = Vec::new();
+    for (i, s) in reader.into_iter().enumerate() {
+        let line = i + 1;
+        let syntax = || FsError::Syntax {
+            line,
+            text: s.clone(),
+        };
+        if let Some(command) = s.strip_prefix("$ ") {
+            let parts = command.splitn(2, ' ').collect::<Vec<_>>();
+            match parts[..] {
+                ["cd", dir] if !dir.is_empty() => {
+                    session.push((line, Command::Cd(dir.to_string())))
                 }
+                ["ls"] => session.push((line, Command::Ls(Vec::new()))),
+                _ => return Err(syntax()),
             }
         } else {
-            unimplemented!("{}", s)
+            let entries = match session.last_mut() {
+                Some((_, Command::Ls(entries))) => entries,
+                _ => return Err(syntax()),
+            };
+            let entry = match s.split_once(' ') {
+                Some(("dir", name)) if !name.is_empty() => Entry::Dir(name.to_string()),
+                Some((size, name)) if !name.is_empty() => {
+                    Entry::File(size.parse().map_err(|_| syntax())?, name.to_string())
+                }
+                _ => return Err(syntax()),
+            };
+            entries.push(entry);
         }
     }
-    None
+    Ok(session)
 }
 
-fn parse(reader: Vec<String>) -> Input {
-    let mut iter = reader.into_iter().peekable();
-    let mut fs = Dir::new();
-    while iter.peek().is_some() {
-        parse_(&mut fs, &mut iter);
+fn replay(session: &[(usize, Command)]) -> Result<Dir, FsError> {
+    let mut root = Dir::new();
+    let mut cwd: Vec<String> = Vec::new();
+    for (line, command) in session {
+        let line = *line;
+        match command {
+            Command::Cd(target) => {
+                if target.starts_with('/') {
+                    cwd.clear();
+                }
+                for name in target.split('/').filter(|s| !s.is_empty()) {
+                    if name == ".." {
+                        cwd.pop();
+                        continue;
+                    }
+                    let dir = root.get_mut(&cwd).unwrap();
+                    if dir.files.contains_key(name) {
+                        let path = join(&cwd, name);
+                        return Err(FsError::NotADirectory { line, path });
+                    }
+                    if dir.listed && !dir.dirs.contains_key(name) {
+                        let path = join(&cwd, name);
+                        return Err(FsError::Contradiction { line, path });
+                    }
+                    dir.dirs.entry(name.to_string()).or_default();
+                    cwd.push(name.to_string());
+                }
+            }
+            Command::Ls(entries) => {
+                let dir = root.get_mut(&cwd).unwrap();
+                dir.list(entries).map_err(|name| FsError::Contradiction {
+                    line,
+                    path: join(&cwd, &name),
+                })?;
+            }
+        }
     }
-    fs
+    Ok(root)
+}
+
+fn parse(reader: Vec<String>) -> Result<Input, FsError> {
+    replay(&parse_session(reader)?)
 }
 
 pub fn seven() -> Result<(), std::io::Error> {
     let file = File::open("input/7_input")?;
     let reader = BufReader::new(file);
-    let input = parse(reader.lines().map(|t| t.unwrap()).collect());
+    let input = parse(reader.lines().map(|t| t.unwrap()).collect())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     println!("Day 7 part 1: {}", one_impl(&input));
-    println!("Day 7 part 2: {}", two_impl(&input));
+    println!("Day 7 part 2: {}", two_impl(70000000, 30000000, &input));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day_7::{one_impl, parse, two_impl};
+    use crate::day_7::{
+        one_impl, parse, sizes, smallest_deletion, smallest_deletion_set, two_impl, FsError,
+    };
 
     #[test]
     fn it_works() {
@@ -127,9 +523,43 @@ mod tests {
             "5626152 d.ext",
             "7214296 k",
         ];
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(95437, one_impl(&parsed));
-        assert_eq!(24933642, two_impl(&parsed));
+        assert_eq!(24933642, two_impl(70000000, 30000000, &parsed));
+        assert_eq!(
+            Some(("/d".to_string(), 24933642)),
+            smallest_deletion(&parsed, 70000000, 30000000)
+        );
+        assert!(sizes(&parsed).1.contains(&("/a/e".to_string(), 584)));
+        assert_eq!(
+            [
+                "- / (dir, size=48381165)",
+                "  - a (dir, size=94853)",
+                "    - e (dir, size=584)",
+                "      - i (file, size=584)",
+                "    - f (file, size=29116)",
+                "    - g (file, size=2557)",
+                "    - h.lst (file, size=62596)",
+                "  - b.txt (file, size=14848514)",
+                "  - c.dat (file, size=8504156)",
+                "  - d (dir, size=24933642)",
+                "    - d.ext (file, size=5626152)",
+                "    - d.log (file, size=8033020)",
+                "    - j (file, size=4060174)",
+                "    - k (file, size=7214296)",
+                "",
+            ]
+            .join("\n"),
+            parsed.render_tree()
+        );
+        assert_eq!(
+            "48381165  /\n24933642    d\n   94853    a\n     584      e\n",
+            parsed.du(None)
+        );
+        assert_eq!(
+            "48381165  /\n24933642    d\n   94853    a\n",
+            parsed.du(Some(1))
+        );
     }
 
     #[test]
@@ -145,7 +575,7 @@ mod tests {
             "$ ls",
             "1 other.txt",
         ];
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(5, one_impl(&parsed));
     }
 
@@ -168,7 +598,131 @@ mod tests {
             "$ ls",
             "1 third.txt",
         ];
-        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
         assert_eq!(7, one_impl(&parsed));
     }
+
+    #[test]
+    fn queries() {
+        let input = [
+            "$ cd /",
+            "$ ls",
+            "dir a",
+            "14848514 b.txt",
+            "8504156 c.dat",
+            "dir d",
+            "$ cd a/e",
+            "$ ls",
+            "584 i",
+            "$ cd /d",
+            "$ ls",
+            "4060174 j",
+            "8033020 d.log",
+            "$ cd ../a",
+            "$ ls",
+            "dir e",
+            "29116 f",
+            "2557 lscd.txt",
+        ];
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
+        assert_eq!(Some(584), parsed.size("/a/e"));
+        assert_eq!(Some(32257), parsed.size("/a"));
+        assert_eq!(Some(2557), parsed.size("/a/lscd.txt"));
+        assert_eq!(None, parsed.size("/x"));
+        assert_eq!(
+            vec![("/".to_string(), 35478121), ("/d".to_string(), 12093194)],
+            parsed.largest_dirs(2)
+        );
+        assert_eq!(
+            vec![
+                ("/a/lscd.txt".to_string(), 2557),
+                ("/b.txt".to_string(), 14848514)
+            ],
+            parsed.find("*.txt")
+        );
+        assert_eq!(vec![("/a/e/i".to_string(), 584)], parsed.find("/a/?/*"));
+    }
+
+    #[test]
+    fn contradictions() {
+        let fs = |input: &[&str]| parse(input.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            Err(FsError::Contradiction {
+                line: 4,
+                path: "/a".to_string()
+            }),
+            fs(&["$ cd /", "$ ls", "dir a", "$ ls", "1 b", "1 a"])
+        );
+        assert_eq!(
+            Err(FsError::Contradiction {
+                line: 3,
+                path: "/b".to_string()
+            }),
+            fs(&["$ ls", "dir a", "$ cd b"])
+        );
+        assert_eq!(
+            Err(FsError::NotADirectory {
+                line: 3,
+                path: "/a".to_string()
+            }),
+            fs(&["$ ls", "1 a", "$ cd a"])
+        );
+        assert_eq!(
+            Err(FsError::Syntax {
+                line: 1,
+                text: "1 a".to_string()
+            }),
+            fs(&["1 a"])
+        );
+    }
+
+    #[test]
+    fn deletion_sets() {
+        let input = [
+            "$ cd /",
+            "$ ls",
+            "dir a",
+            "dir b",
+            "dir c",
+            "$ cd a",
+            "$ ls",
+            "60 x",
+            "$ cd ../b",
+            "$ ls",
+            "40 y",
+            "dir d",
+            "$ cd d",
+            "$ ls",
+            "25 z",
+            "$ cd /c",
+            "$ ls",
+            "100 w",
+        ];
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect()).unwrap();
+        assert_eq!(
+            Some(("/c".to_string(), 100)),
+            smallest_deletion(&parsed, 300, 155)
+        );
+        assert_eq!(
+            Some((85, vec!["/a".to_string(), "/b/d".to_string()])),
+            smallest_deletion_set(&parsed, 300, 155)
+        );
+        assert_eq!(Some((0, vec![])), smallest_deletion_set(&parsed, 300, 50));
+        assert_eq!(None, smallest_deletion_set(&parsed, 300, 400));
+
+        let scaled = input
+            .iter()
+            .map(|s| match s.split_once(' ') {
+                Some((size, name)) if size.parse::<usize>().is_ok() => {
+                    format!("{}000 {}", size, name)
+                }
+                _ => s.to_string(),
+            })
+            .collect();
+        let parsed = parse(scaled).unwrap();
+        assert_eq!(
+            Some((85000, vec!["/a".to_string(), "/b/d".to_string()])),
+            smallest_deletion_set(&parsed, 300000, 155000)
+        );
+    }
 }
@@ -2,85 +2,134 @@ use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
-use crate::grid::Grid;
+use crate::grid::{heat, Ansi, Grid};
 
 type Input = Grid<i32>;
 
-fn ray(trees: &Input, target: &mut Grid<bool>, (mut x, mut y): (i32, i32), (dx, dy): (i32, i32)) {
-    let mut max = i32::MIN;
-    while let Some(tree) = trees.get(x as usize, y as usize) {
-        if *tree > max {
-            max = *tree;
-            target.set(x as usize, y as usize, true);
+fn lines(trees: &Input) -> Vec<Vec<(usize, usize)>> {
+    let (width, height) = (trees.row_size(), trees.col_size());
+    let rows = (0..height).map(|y| (0..width).map(|x| (x, y)).collect::<Vec<_>>());
+    let cols = (0..width).map(|x| (0..height).map(|y| (x, y)).collect::<Vec<_>>());
+    rows.chain(cols)
+        .flat_map(|line| {
+            let mut reversed = line.clone();
+            reversed.reverse();
+            [line, reversed]
+        })
+        .collect()
+}
+
+fn sweep<F>(trees: &Input, mut f: F)
+where
+    F: FnMut((usize, usize), usize, bool),
+{
+    for line in lines(trees) {
+        let height = |i: usize| trees.get(line[i].0, line[i].1).unwrap();
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, &pos) in line.iter().enumerate() {
+            while stack
+                .last()
+                .filter(|top| height(**top) < height(i))
+                .is_some()
+            {
+                stack.pop();
+            }
+            match stack.last() {
+                Some(top) => f(pos, i - top, false),
+                None => f(pos, i, true),
+            }
+            stack.push(i);
         }
-        x += dx;
-        y += dy;
     }
 }
 
-fn ray2(trees: &Input, (mut x, mut y): (i32, i32), (dx, dy): (i32, i32)) -> i32 {
-    let max = trees.get(x as usize, y as usize).unwrap();
-    x += dx;
-    y += dy;
-    let mut count = 0;
-    while let Some(tree) = trees.get(x as usize, y as usize) {
-        count += 1;
-        if tree >= max {
-            break;
+fn visibility(trees: &Input) -> Grid<bool> {
+    let mut grid = trees.map(|_| false);
+    sweep(trees, |(x, y), _, visible| {
+        if visible {
+            grid.set(x, y, true);
         }
-        x += dx;
-        y += dy;
-    }
-    count
+    });
+    grid
 }
 
-fn one_impl(input: &Input) -> usize {
-    let mut grid = input.map(|_| false);
-    for y in 0..input.col_size() {
-        ray(input, &mut grid, (0, y as i32), (1, 0));
-        ray(
-            input,
-            &mut grid,
-            (input.row_size() as i32 - 1, y as i32),
-            (-1, 0),
-        );
-    }
+fn scenic_scores(trees: &Input) -> Grid<u64> {
+    let mut grid = trees.map(|_| 1);
+    sweep(trees, |(x, y), distance, _| {
+        let score = grid.get(x, y).unwrap() * distance as u64;
+        grid.set(x, y, score);
+    });
+    grid
+}
 
-    for x in 0..input.row_size() {
-        ray(input, &mut grid, (x as i32, 0), (0, 1));
-        ray(
-            input,
-            &mut grid,
-            (x as i32, input.col_size() as i32 - 1),
-            (0, -1),
-        );
-    }
+#[allow(dead_code)]
+fn visibility_pgm(trees: &Input) -> String {
+    visibility(trees).to_pgm(|visible| if *visible { 255 } else { 0 })
+}
 
-    grid.iter().map(|c| *c as usize).sum()
+#[allow(dead_code)]
+fn scenic_pgm(trees: &Input) -> String {
+    let scores = scenic_scores(trees);
+    let max = scores.iter().copied().max().unwrap_or(0);
+    scores.to_pgm(|score| heat(*score, max).0)
 }
 
-fn two_impl(input: &Input) -> i32 {
-    let mut max = 0;
-    for y in 1..input.col_size() - 1 {
-        for x in 1..input.row_size() - 1 {
-            let mut res = 1;
+#[allow(dead_code)]
+fn scenic_ppm(trees: &Input) -> String {
+    let scores = scenic_scores(trees);
+    let max = scores.iter().copied().max().unwrap_or(0);
+    scores.to_ppm(|score| heat(*score, max))
+}
 
-            res *= ray2(input, (x as i32, y as i32), (1, 0));
-            res *= ray2(input, (x as i32, y as i32), (-1, 0));
-            res *= ray2(input, (x as i32, y as i32), (0, 1));
-            res *= ray2(input, (x as i32, y as i32), (0, -1));
+#[allow(dead_code)]
+fn ansi_visibility(trees: &Input) -> String {
+    let visible = visibility(trees);
+    trees
+        .map_pos(|height, x, y| match visible.get(x, y) {
+            Some(true) => Ansi(*height, (0, 128, 0)),
+            _ => Ansi(*height, (64, 64, 64)),
+        })
+        .to_string()
+}
 
-            max = max.max(res);
-        }
-    }
-    max
+#[allow(dead_code)]
+fn ansi_scenic(trees: &Input) -> String {
+    let scores = scenic_scores(trees);
+    let max = scores.iter().copied().max().unwrap_or(0);
+    trees
+        .map_pos(|height, x, y| Ansi(*height, heat(*scores.get(x, y).unwrap(), max)))
+        .to_string()
+}
+
+#[allow(dead_code)]
+fn export_heatmaps(trees: &Input, prefix: &str) -> Result<(), std::io::Error> {
+    std::fs::write(format!("{}_visibility.pgm", prefix), visibility_pgm(trees))?;
+    std::fs::write(format!("{}_scenic.pgm", prefix), scenic_pgm(trees))?;
+    std::fs::write(format!("{}_scenic.ppm", prefix), scenic_ppm(trees))?;
+    Ok(())
+}
+
+fn one_impl(input: &Input) -> usize {
+    visibility(input).iter().filter(|c| **c).count()
+}
+
+fn two_impl(input: &Input) -> u64 {
+    scenic_scores(input).iter().copied().max().unwrap_or(0)
 }
 
 fn parse(reader: Vec<String>) -> Input {
     Grid::new(
         reader
             .into_iter()
-            .map(|c| c.chars().map(|c| c.to_digit(10).unwrap() as i32).collect())
+            .map(|row| {
+                if row.contains(char::is_whitespace) {
+                    row.split_whitespace().map(|c| c.parse().unwrap()).collect()
+                } else {
+                    row.chars()
+                        .map(|c| c.to_digit(10).unwrap() as i32)
+                        .collect()
+                }
+            })
             .collect(),
     )
 }
@@ -96,7 +145,11 @@ pub fn eight() -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::day_8::{one_impl, parse, two_impl};
+    use crate::day_8::{
+        ansi_scenic, ansi_visibility, one_impl, parse, scenic_pgm, scenic_ppm, scenic_scores,
+        two_impl, visibility, visibility_pgm,
+    };
+    use crate::grid::Grid;
 
     #[test]
     fn it_works() {
@@ -105,4 +158,52 @@ mod tests {
         assert_eq!(21, one_impl(&parsed));
         assert_eq!(8, two_impl(&parsed));
     }
+
+    #[test]
+    fn grids() {
+        let parsed = parse(vec!["30373".to_string(), "25512".to_string()]);
+        assert_eq!(10, one_impl(&parsed));
+        assert_eq!(0, two_impl(&parsed));
+
+        let parsed = parse(vec!["5".to_string()]);
+        assert_eq!(Grid::new(vec![vec![true]]), visibility(&parsed));
+        assert_eq!(Grid::new(vec![vec![0]]), scenic_scores(&parsed));
+
+        let input = ["10 10 10 10", "10 11 42 10", "10 12 10 10"];
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            Grid::new(vec![
+                vec![true, true, true, true],
+                vec![true, true, true, true],
+                vec![true, true, true, true],
+            ]),
+            visibility(&parsed)
+        );
+        assert_eq!(
+            Grid::new(vec![vec![0, 0, 0, 0], vec![0, 1, 2, 0], vec![0, 0, 0, 0]]),
+            scenic_scores(&parsed)
+        );
+    }
+
+    #[test]
+    fn heatmaps() {
+        let parsed = parse(vec!["121".to_string(), "191".to_string()]);
+        assert_eq!(
+            "P2\n3 2\n255\n255 255 255\n255 255 255\n",
+            visibility_pgm(&parsed)
+        );
+        assert_eq!("P2\n3 2\n255\n0 0 0\n0 0 0\n", scenic_pgm(&parsed));
+        assert!(scenic_ppm(&parsed).starts_with("P3\n3 2\n255\n0 0 0 0 0 0"));
+
+        let parsed = parse(vec![
+            "30373".to_string(),
+            "25512".to_string(),
+            "65332".to_string(),
+        ]);
+        let ansi = ansi_visibility(&parsed);
+        assert_eq!(3, ansi.lines().count());
+        assert!(ansi.starts_with("\x1b[48;2;0;128;0m3\x1b[0m"));
+        assert!(ansi.contains("\x1b[48;2;64;64;64m1\x1b[0m"));
+        assert!(ansi_scenic(&parsed).contains("\x1b[48;2;255;255;255m5\x1b[0m"));
+    }
 }
@@ -3,66 +3,178 @@ use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 
-#[derive(Debug, Clone, Copy)]
+use crate::grid::Grid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
-type Input = Vec<(Direction, usize)>;
-
-fn move_head(head: (i32, i32), direction: Direction) -> (i32, i32) {
-    match direction {
-        Direction::Up => (head.0, head.1 + 1),
-        Direction::Down => (head.0, head.1 - 1),
-        Direction::Left => (head.0 - 1, head.1),
-        Direction::Right => (head.0 + 1, head.1),
+impl Direction {
+    fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
     }
 }
 
-fn move_tail(head: (i32, i32), tail: (i32, i32)) -> (i32, i32) {
-    let table = vec![
-        vec![(-1, -1), (-1, -1), (-0, -1), (1, -1), (1, -1)],
-        vec![(-1, -1), (-0, -0), (-0, -0), (0, -0), (1, -1)],
-        vec![(-1, -0), (-0, -0), (-0, -0), (0, -0), (1, -0)],
-        vec![(-1, 1), (-0, 0), (-0, 0), (0, 0), (1, 1)],
-        vec![(-1, 1), (-1, 1), (-0, 1), (1, 1), (1, 1)],
-    ];
-    let d = table[(head.1 - tail.1 + 2) as usize][(head.0 - tail.0 + 2) as usize];
-    (tail.0 + d.0, tail.1 + d.1)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    Diagonal,
+    Orthogonal,
 }
 
-fn one_impl(input: &Input) -> usize {
-    let mut history: HashSet<(i32, i32)> = HashSet::new();
-    let mut tail = (0, 0);
-    let mut head = (0, 0);
-    history.insert(tail);
-    for (dir, count) in input {
-        for _ in 0..*count {
-            head = move_head(head, *dir);
-            tail = move_tail(head, tail);
-            history.insert(tail);
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frames {
+    Steps,
+    Instructions,
+    Final,
+}
+
+type Input = Vec<(Direction, usize)>;
+type Bounds = ((i32, i32), (i32, i32));
+
+#[derive(Debug, Clone)]
+struct Rope {
+    knots: Vec<(i32, i32)>,
+    slack: i32,
+    follow: Follow,
+    visited: Vec<HashSet<(i32, i32)>>,
+}
+
+impl Rope {
+    fn new(knots: usize, slack: i32, follow: Follow) -> Rope {
+        assert!(knots > 0 && slack > 0);
+        Rope {
+            knots: vec![(0, 0); knots],
+            slack,
+            follow,
+            visited: vec![collection! {(0, 0)}; knots],
+        }
+    }
+
+    fn follow_step(&self, leader: (i32, i32), knot: (i32, i32)) -> Option<(i32, i32)> {
+        let (dx, dy) = (leader.0 - knot.0, leader.1 - knot.1);
+        if dx.abs().max(dy.abs()) <= self.slack {
+            return None;
+        }
+        let (sx, sy) = match self.follow {
+            Follow::Diagonal => (dx.signum(), dy.signum()),
+            Follow::Orthogonal if dx.abs() >= dy.abs() => (dx.signum(), 0),
+            Follow::Orthogonal => (0, dy.signum()),
+        };
+        Some((knot.0 + sx, knot.1 + sy))
+    }
+
+    fn step(&mut self, (dx, dy): (i32, i32)) {
+        self.knots[0] = (self.knots[0].0 + dx, self.knots[0].1 + dy);
+        self.visited[0].insert(self.knots[0]);
+        for i in 1..self.knots.len() {
+            while let Some(next) = self.follow_step(self.knots[i - 1], self.knots[i]) {
+                self.knots[i] = next;
+                self.visited[i].insert(next);
+            }
+        }
+    }
+
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            _ if self.knots.len() == 2 => 'T',
+            k => std::char::from_digit(k as u32, 36).unwrap_or('?'),
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        let all = self.visited.iter().flatten();
+        let (xs, ys): (Vec<i32>, Vec<i32>) = all.copied().unzip();
+        (
+            (*xs.iter().min().unwrap(), *ys.iter().min().unwrap()),
+            (*xs.iter().max().unwrap(), *ys.iter().max().unwrap()),
+        )
+    }
+
+    fn render(&self, ((min_x, min_y), (max_x, max_y)): Bounds, knots: bool, trail: bool) -> String {
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        Grid::new_with(width, height, |x, y| {
+            let pos = (min_x + x as i32, max_y - y as i32);
+            let knot = self.knots.iter().position(|k| *k == pos);
+            match knot {
+                Some(k) if knots => self.label(k),
+                _ if pos == (0, 0) => 's',
+                _ if trail && self.visited.last().unwrap().contains(&pos) => '#',
+                _ => '.',
+            }
+        })
+        .to_string()
+    }
+
+    fn run(&mut self, input: &Input) {
+        for (dir, count) in input {
+            for _ in 0..*count {
+                self.step(dir.delta());
+            }
         }
     }
-    history.len()
 }
 
-fn two_impl(input: &Input) -> usize {
-    let mut history: HashSet<(i32, i32)> = HashSet::new();
-    let mut chain: Vec<(i32, i32)> = (0..10).map(|_| (0, 0)).collect();
-    history.insert((0, 0));
+#[allow(dead_code)]
+fn replay(rope: &Rope, input: &Input, frames: Frames, knots: bool, trail: bool) -> Vec<String> {
+    let mut finished = rope.clone();
+    finished.run(input);
+    let window = finished.bounds();
+
+    let mut rope = rope.clone();
+    let mut out = Vec::new();
+    if frames != Frames::Final {
+        out.push(rope.render(window, knots, trail));
+    }
     for (dir, count) in input {
         for _ in 0..*count {
-            chain[0] = move_head(chain[0], *dir);
-            for i in 1..chain.len() {
-                chain[i] = move_tail(chain[i - 1], chain[i]);
+            rope.step(dir.delta());
+            if frames == Frames::Steps {
+                out.push(rope.render(window, knots, trail));
             }
-            history.insert(*chain.last().unwrap());
         }
+        if frames == Frames::Instructions {
+            out.push(rope.render(window, knots, trail));
+        }
+    }
+    if frames == Frames::Final {
+        out.push(rope.render(window, knots, trail));
     }
-    history.len()
+    out
+}
+
+fn simulate(input: &Input, knots: usize, slack: i32, follow: Follow) -> Vec<HashSet<(i32, i32)>> {
+    let mut rope = Rope::new(knots, slack, follow);
+    rope.run(input);
+    rope.visited
+}
+
+fn one_impl(input: &Input) -> usize {
+    simulate(input, 2, 1, Follow::Diagonal)[1].len()
+}
+
+fn two_impl(input: &Input) -> usize {
+    simulate(input, 10, 1, Follow::Diagonal)[9].len()
 }
 
 fn parse(reader: Vec<String>) -> Input {
@@ -76,6 +188,10 @@ fn parse(reader: Vec<String>) -> Input {
                     "D" => Direction::Down,
                     "L" => Direction::Left,
                     "R" => Direction::Right,
+                    "UL" => Direction::UpLeft,
+                    "UR" => Direction::UpRight,
+                    "DL" => Direction::DownLeft,
+                    "DR" => Direction::DownRight,
                     other => unimplemented!("{}", other),
                 },
                 split[1].parse().unwrap(),
@@ -95,7 +211,7 @@ pub fn nine() -> Result<(), std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::day_9::{one_impl, parse, two_impl};
+    use crate::day_9::{one_impl, parse, replay, simulate, two_impl, Follow, Frames, Rope};
 
     #[test]
     fn it_works() {
@@ -135,4 +251,50 @@ U 20
         let parsed = parse(input.iter().map(|s| s.to_string()).collect());
         assert_eq!(36, two_impl(&parsed));
     }
+
+    #[test]
+    fn rope_rules() {
+        let input = vec!["UR 3".to_string(), "DR 1".to_string()];
+        let parsed = parse(input);
+        let visited = simulate(&parsed, 3, 1, Follow::Diagonal);
+        assert_eq!(5, visited[0].len());
+        assert_eq!(collection! {(0, 0), (1, 1), (2, 2), (3, 2)}, visited[1]);
+        assert_eq!(collection! {(0, 0), (1, 1), (2, 2)}, visited[2]);
+
+        let parsed = parse(vec!["R 4".to_string()]);
+        let visited = simulate(&parsed, 2, 2, Follow::Diagonal);
+        assert_eq!(collection! {(0, 0), (1, 0), (2, 0)}, visited[1]);
+
+        let mut rope = Rope::new(3, 1, Follow::Orthogonal);
+        rope.step((3, 2));
+        assert_eq!(vec![(3, 2), (2, 1), (1, 0)], rope.knots);
+        assert_eq!(
+            collection! {(0, 0), (1, 0), (2, 0), (2, 1)},
+            rope.visited[1]
+        );
+        assert_eq!(collection! {(0, 0), (1, 0)}, rope.visited[2]);
+    }
+
+    #[test]
+    fn replays() {
+        let input = ["R 4", "U 4", "L 3", "D 1", "R 4", "D 1", "L 5", "R 2"];
+        let parsed = parse(input.iter().map(|s| s.to_string()).collect());
+
+        let rope = Rope::new(2, 1, Follow::Diagonal);
+        let frames = replay(&rope, &parsed, Frames::Instructions, true, false);
+        assert_eq!(9, frames.len());
+        assert_eq!("......\n......\n......\n......\nH.....\n", frames[0]);
+        assert_eq!("......\n......\n......\n......\ns..TH.\n", frames[1]);
+        assert_eq!("....H.\n....T.\n......\n......\ns.....\n", frames[2]);
+        assert_eq!(
+            vec!["..##..\n...##.\n.####.\n....#.\ns###..\n"],
+            replay(&rope, &parsed, Frames::Final, false, true)
+        );
+        assert_eq!(25, replay(&rope, &parsed, Frames::Steps, true, true).len());
+
+        let rope = Rope::new(10, 1, Follow::Diagonal);
+        let frames = replay(&rope, &parsed, Frames::Instructions, true, false);
+        assert_eq!("......\n......\n......\n......\n4321H.\n", frames[1]);
+        assert_eq!("....H.\n....1.\n..432.\n.5....\n6.....\n", frames[2]);
+    }
 }
@@ -22,7 +22,7 @@ impl<Cell> Grid<Cell> {
     }
 
     pub fn row_size(&self) -> usize {
-        self.elems.get(0).map(|row| row.len()).unwrap_or(0)
+        self.elems.first().map(|row| row.len()).unwrap_or(0)
     }
 
     pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
@@ -119,6 +119,49 @@ impl<Cell> Grid<Cell> {
             .flat_map(move |y| (0..self.row_size()).map(move |x| (x, y)))
             .map(move |(x, y)| self.get(x, y).unwrap())
     }
+
+    pub fn to_pgm<F: FnMut(&Cell) -> u8>(&self, mut f: F) -> String {
+        let mut out = format!("P2\n{} {}\n255\n", self.row_size(), self.col_size());
+        for row in &self.elems {
+            let row = row.iter().map(|c| f(c).to_string()).collect::<Vec<_>>();
+            out.push_str(&row.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_ppm<F: FnMut(&Cell) -> (u8, u8, u8)>(&self, mut f: F) -> String {
+        let mut out = format!("P3\n{} {}\n255\n", self.row_size(), self.col_size());
+        for row in &self.elems {
+            let row = row
+                .iter()
+                .map(|c| {
+                    let (r, g, b) = f(c);
+                    format!("{} {} {}", r, g, b)
+                })
+                .collect::<Vec<_>>();
+            out.push_str(&row.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[allow(dead_code)]
+pub fn heat(value: u64, max: u64) -> (u8, u8, u8) {
+    let t = (value * 765).checked_div(max).unwrap_or(0);
+    let channel = |from: u64| (t.saturating_sub(from)).min(255) as u8;
+    (channel(0), channel(255), channel(510))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ansi<T>(pub T, pub (u8, u8, u8));
+
+impl<T: fmt::Display> fmt::Display for Ansi<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (r, g, b) = self.1;
+        write!(f, "\x1b[48;2;{};{};{}m{}\x1b[0m", r, g, b, self.0)
+    }
 }
 
 impl<Cell: fmt::Display> fmt::Display for Grid<Cell> {
@@ -169,4 +212,21 @@ mod tests {
         assert_eq!(Some(&0), grid.ray(-1, 4, 1, -1, |n| *n == 1));
         assert_eq!(Some(&0), grid.ray(-1, 1, 1, 0, |n| *n == 1));
     }
+
+    #[test]
+    fn images() {
+        let grid = Grid::new(vec![vec![0, 1, 2], vec![2, 1, 0]]);
+        assert_eq!("P2\n3 2\n255\n0 1 2\n2 1 0\n", grid.to_pgm(|n| *n as u8));
+        assert_eq!(
+            "P3\n3 2\n255\n0 0 0 0 0 0 255 255 255\n255 255 255 0 0 0 0 0 0\n",
+            grid.to_ppm(|n| heat(*n / 2, 1))
+        );
+        assert_eq!((0, 0, 0), heat(0, 0));
+        assert_eq!((255, 0, 0), heat(1, 3));
+        assert_eq!((255, 255, 0), heat(2, 3));
+        assert_eq!(
+            "\x1b[48;2;1;2;3m7\x1b[0m\n",
+            format!("{}", Grid::new(vec![vec![Ansi(7, (1, 2, 3))]]))
+        );
+    }
 }